@@ -0,0 +1,80 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use log_parser::runner::Runner;
+use std::io::{sink, Cursor};
+
+/// How many times the test logs are repeated, so that the time spent setting
+/// up the runner doesn't dominate.
+const REPEAT: usize = 200;
+
+/// How many lines the generated log of a node following the tip has.
+const CHATTER_LINES: usize = 200_000;
+
+/// A log of a node following the tip, as most of the lines of long-running
+/// nodes are: transaction pool and peer chatter, with only one line in a
+/// hundred that produces an event. It shows what the prefilter saves on the
+/// lines that don't.
+fn chatter() -> String {
+    let mut log = String::new();
+    for line in 0..CHATTER_LINES {
+        let (minute, second) = (line / 6000 % 60, line / 100 % 60);
+        let timestamp = format!("2024-06-07T09:{minute:02}:{second:02}.{line:06}Z");
+        let message = match line % 100 {
+            0 => format!(
+                "INFO reth_node_events::node: Block added to canonical chain number={} hash=0x{line:064x} peers=87 txs=152 gas=14.25 Mgas gas_throughput=101.3Mgas/second full=47.5% base_fee=4.12gwei blobs=3 excess_blobs=0 elapsed=140.7ms",
+                20_000_000 + line / 100
+            ),
+            n if n % 3 == 0 => format!(
+                "DEBUG net::session: Session established remote_addr=10.0.{}.{}:30303 peer_id=0x{line:0128x} client_version=Geth/v1.14.5-stable",
+                line % 256,
+                n
+            ),
+            n if n % 3 == 1 => format!(
+                "DEBUG txpool: Transaction pool maintained pending={} queued={} block=0x{line:064x}",
+                line % 5000,
+                n
+            ),
+            n => format!(
+                "TRACE net::tx: Received transactions peer_id=0x{line:0128x} count={n} hashes=[0x{line:064x}]"
+            ),
+        };
+        log.push_str(&format!("{timestamp} {message}\n"));
+    }
+    log
+}
+
+fn bench_process(c: &mut Criterion) {
+    let mut group = c.benchmark_group("process");
+    for (name, corpus) in [
+        (
+            "text",
+            include_str!("../tests/data/input-print-summary-multiple-pipelines.txt").repeat(REPEAT),
+        ),
+        (
+            "json",
+            include_str!("../tests/data/input-print-summary-reth-json.txt").repeat(REPEAT),
+        ),
+        ("chatter", chatter()),
+    ] {
+        let corpus = corpus.into_bytes();
+        group.throughput(Throughput::Bytes(corpus.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || Cursor::new(corpus.clone()),
+                |reader| {
+                    Runner::builder()
+                        .with_reader(reader)
+                        .with_stdout_writer(sink())
+                        .build()
+                        .unwrap()
+                        .run()
+                        .unwrap()
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_process);
+criterion_main!(benches);
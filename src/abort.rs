@@ -0,0 +1,137 @@
+use crate::{pipeline::Interruption, time::format_time};
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// How many lines following a report are kept, so that a runaway backtrace
+/// doesn't grow the state without bounds.
+const REPORT_LINES_KEPT: usize = 200;
+
+/// How many lines of a backtrace are printed in the summary, the whole of it
+/// being exported.
+const BACKTRACE_LINES_LISTED: usize = 5;
+
+/// An abnormal termination of reth, with what it or its supervisor reported.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Abort {
+    pub reason: Interruption,
+    pub timestamp: Option<SystemTime>,
+    /// The stage which was running, if any.
+    pub stage: Option<String>,
+    /// The line reporting the termination, e.g.
+    /// `thread 'tokio-runtime-worker' panicked at crates/stages/src/lib.rs:10:5:`.
+    pub report: String,
+    /// Lines following the report: the panic message or the chain of errors.
+    pub message: Vec<String>,
+    /// Lines of the backtrace printed after a panic, when enabled.
+    pub backtrace: Vec<String>,
+}
+
+impl Abort {
+    pub fn new(
+        reason: Interruption,
+        timestamp: Option<SystemTime>,
+        stage: Option<&str>,
+        report: &str,
+    ) -> Self {
+        Abort {
+            reason,
+            timestamp,
+            stage: stage.map(str::to_string),
+            report: report.trim().to_string(),
+            message: Vec::new(),
+            backtrace: Vec::new(),
+        }
+    }
+
+    /// Adds a line following the report, which goes to the backtrace once
+    /// its header was seen. Hints about enabling backtraces are skipped.
+    pub(crate) fn append(&mut self, line: &str) {
+        let line = line.trim_end();
+        if line.trim().is_empty()
+            || line.starts_with("note: ")
+            || self.message.len() + self.backtrace.len() >= REPORT_LINES_KEPT
+        {
+            return;
+        }
+        if !self.backtrace.is_empty() || line == "stack backtrace:" {
+            self.backtrace.push(line.to_string());
+        } else {
+            self.message.push(line.to_string());
+        }
+    }
+
+    pub(crate) fn print<W: std::io::Write>(&self, writer: &mut W) {
+        let mut header = format!("  Aborted: {}", self.reason.name());
+        if let Some(stage) = &self.stage {
+            header.push_str(&format!(" during {stage}"));
+        }
+        if self.timestamp.is_some() {
+            header.push_str(&format!(" at {}", format_time(self.timestamp)));
+        }
+        writeln!(writer, "{header}").unwrap();
+        writeln!(writer, "    {}", self.report).unwrap();
+        for line in &self.message {
+            writeln!(writer, "    {line}").unwrap();
+        }
+        for line in self.backtrace.iter().take(BACKTRACE_LINES_LISTED) {
+            writeln!(writer, "    {line}").unwrap();
+        }
+        if self.backtrace.len() > BACKTRACE_LINES_LISTED {
+            writeln!(
+                writer,
+                "    ... {} more lines",
+                self.backtrace.len() - BACKTRACE_LINES_LISTED
+            )
+            .unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_abort() {
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(1_718_000_000);
+        let mut abort = Abort::new(
+            Interruption::Panic,
+            Some(timestamp),
+            Some("Execution"),
+            "thread 'tokio-runtime-worker' panicked at crates/stages/src/lib.rs:10:5:",
+        );
+        abort.append("called `Option::unwrap()` on a `None` value");
+        abort.append("");
+        abort.append("stack backtrace:");
+        for frame in 0..6 {
+            abort.append(&format!("   {frame}: reth::frame"));
+        }
+        abort.append("note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.");
+        assert_eq!(
+            abort.message,
+            ["called `Option::unwrap()` on a `None` value"]
+        );
+        assert_eq!(abort.backtrace.len(), 7);
+
+        let mut output = Vec::new();
+        abort.print(&mut output);
+        assert_eq!(
+            String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "  Aborted: panic during Execution at 2024-06-10 06:13:20",
+                "    thread 'tokio-runtime-worker' panicked at crates/stages/src/lib.rs:10:5:",
+                "    called `Option::unwrap()` on a `None` value",
+                "    stack backtrace:",
+                "       0: reth::frame",
+                "       1: reth::frame",
+                "       2: reth::frame",
+                "       3: reth::frame",
+                "    ... 2 more lines",
+            ]
+        );
+    }
+}
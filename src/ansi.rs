@@ -0,0 +1,77 @@
+/// Removes ANSI escape sequences, such as the colors reth uses for levels and
+/// field names when writing to a terminal, from `line`.
+///
+/// Returns `None` when the line has no escape sequence, so the common case
+/// doesn't allocate.
+pub(crate) fn strip_ansi(line: &str) -> Option<String> {
+    if !line.contains('\x1b') {
+        return None;
+    }
+
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            // CSI: parameter and intermediate bytes up to a final byte in
+            // `@`..=`~`, SGR color codes being the most common ones.
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: terminated by BEL or by the `ESC \` string terminator.
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // nF escapes, like charset selection: intermediate bytes followed
+            // by a final byte.
+            Some(c) if (' '..='/').contains(&c) => {
+                for c in chars.by_ref() {
+                    if !(' '..='/').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // Any other escape is a single character long.
+            _ => {}
+        }
+    }
+
+    Some(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("no escapes"), None);
+        assert_eq!(
+            strip_ansi("2024-06-07T09:05:20.873354Z \x1b[32m INFO\x1b[0m Preparing stage \x1b[3mpipeline_stages\x1b[0m\x1b[2m=\x1b[0m1/12 \x1b[3mstage\x1b[0m\x1b[2m=\x1b[0mHeaders"),
+            Some(
+                "2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            strip_ansi("\x1b]0;title\x07a\x1b]8;;link\x1b\\b\x1b(Bc\x1b[1;31"),
+            Some("abc".to_string())
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{
+    stats::percentile,
+    time::{format_duration, format_elapsed, format_time},
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// Silences between consensus layer messages longer than this are reported as
+/// gaps, a few slots without any message being suspicious already.
+const GAP_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// How many of the longest gaps are listed.
+const LONGEST_GAPS_LISTED: usize = 5;
+
+/// How many of the latest calls are searched for the `newPayload` a canonical
+/// block or an invalid block error answers.
+const PENDING_PAYLOADS_SEARCHED: usize = 64;
+
+/// Engine API methods the consensus layer calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EngineMethod {
+    ForkchoiceUpdated,
+    NewPayload,
+}
+
+impl EngineMethod {
+    fn name(self) -> &'static str {
+        match self {
+            EngineMethod::ForkchoiceUpdated => "forkchoiceUpdated",
+            EngineMethod::NewPayload => "newPayload",
+        }
+    }
+}
+
+/// Status reth answered an Engine API call with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PayloadStatus {
+    Valid,
+    Syncing,
+    Invalid,
+}
+
+impl PayloadStatus {
+    const ALL: [PayloadStatus; 3] = [
+        PayloadStatus::Valid,
+        PayloadStatus::Syncing,
+        PayloadStatus::Invalid,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            PayloadStatus::Valid => "VALID",
+            PayloadStatus::Syncing => "SYNCING",
+            PayloadStatus::Invalid => "INVALID",
+        }
+    }
+}
+
+/// An Engine API call from the consensus layer, as logged by reth.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineCall {
+    pub method: EngineMethod,
+    pub timestamp: SystemTime,
+    /// `None` for a `newPayload` whose outcome wasn't logged (yet).
+    pub status: Option<PayloadStatus>,
+    /// Number of the payload, or of the head block for `forkchoiceUpdated`.
+    pub block: Option<u64>,
+    /// Hash of the payload, or of the head block for `forkchoiceUpdated`.
+    pub hash: Option<String>,
+    /// Time reth took to handle the call, in seconds. `newPayload` calls are
+    /// timed up to the block being added to the canonical chain.
+    pub elapsed: Option<f64>,
+}
+
+impl EngineCall {
+    /// Method and status of the call a message logged by reth tells about, if
+    /// any. Outcomes of `newPayload` calls are recorded with
+    /// [`EngineCall::resolve`] instead.
+    pub(crate) fn classify(message: &str) -> Option<(EngineMethod, Option<PayloadStatus>)> {
+        match message {
+            "Forkchoice updated" => {
+                Some((EngineMethod::ForkchoiceUpdated, Some(PayloadStatus::Valid)))
+            }
+            "Received forkchoice updated message when syncing" => Some((
+                EngineMethod::ForkchoiceUpdated,
+                Some(PayloadStatus::Syncing),
+            )),
+            "Received invalid forkchoice updated message" => Some((
+                EngineMethod::ForkchoiceUpdated,
+                Some(PayloadStatus::Invalid),
+            )),
+            "Received block from consensus engine" => Some((EngineMethod::NewPayload, None)),
+            "Invalid block error on new payload" => {
+                Some((EngineMethod::NewPayload, Some(PayloadStatus::Invalid)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Records a call, or the outcome of a `newPayload` received before for
+    /// the same block.
+    pub(crate) fn record(calls: &mut Vec<EngineCall>, call: EngineCall) {
+        if let (EngineMethod::NewPayload, Some(status)) = (call.method, call.status) {
+            if Self::resolve(calls, call.hash.as_deref(), call.timestamp, status) {
+                return;
+            }
+        }
+        calls.push(call);
+    }
+
+    /// Sets the outcome of the latest pending `newPayload` for block `hash`,
+    /// timing it up to `timestamp`. Returns whether one was found.
+    pub(crate) fn resolve(
+        calls: &mut [EngineCall],
+        hash: Option<&str>,
+        timestamp: SystemTime,
+        status: PayloadStatus,
+    ) -> bool {
+        let Some(hash) = hash else {
+            return false;
+        };
+        let Some(call) = calls
+            .iter_mut()
+            .rev()
+            .take(PENDING_PAYLOADS_SEARCHED)
+            .find(|call| {
+                call.method == EngineMethod::NewPayload
+                    && call.status.is_none()
+                    && call.hash.as_deref() == Some(hash)
+            })
+        else {
+            return false;
+        };
+        call.status = Some(status);
+        call.elapsed = timestamp
+            .duration_since(call.timestamp)
+            .ok()
+            .map(|elapsed| elapsed.as_secs_f64());
+        true
+    }
+}
+
+/// A silence of the consensus layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineGap {
+    pub start: SystemTime,
+    pub end: SystemTime,
+}
+
+impl EngineGap {
+    pub fn duration(&self) -> Duration {
+        self.end.duration_since(self.start).unwrap_or_default()
+    }
+}
+
+/// Calls of one Engine API method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodSummary {
+    pub method: EngineMethod,
+    /// Number of calls per status, `None` for unknown ones.
+    pub statuses: Vec<(Option<PayloadStatus>, usize)>,
+    /// p50, p90, p99 and max latency, when the calls were timed.
+    pub latency: Option<[f64; 4]>,
+}
+
+/// Counts, statuses and latencies of the Engine API calls, and the gaps
+/// between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineSummary {
+    pub methods: Vec<MethodSummary>,
+    /// Gaps longer than [`GAP_THRESHOLD`], longest first.
+    pub gaps: Vec<EngineGap>,
+}
+
+impl EngineSummary {
+    /// Summarises the calls, `None` if there are none.
+    pub fn new(calls: &[EngineCall]) -> Option<Self> {
+        if calls.is_empty() {
+            return None;
+        }
+
+        let mut methods = Vec::new();
+        for method in [EngineMethod::ForkchoiceUpdated, EngineMethod::NewPayload] {
+            let calls: Vec<&EngineCall> =
+                calls.iter().filter(|call| call.method == method).collect();
+            if calls.is_empty() {
+                continue;
+            }
+            let statuses = PayloadStatus::ALL
+                .iter()
+                .map(|status| Some(*status))
+                .chain([None])
+                .map(|status| {
+                    (
+                        status,
+                        calls.iter().filter(|call| call.status == status).count(),
+                    )
+                })
+                .filter(|(_, count)| *count > 0)
+                .collect();
+
+            let mut elapsed: Vec<f64> = calls.iter().filter_map(|call| call.elapsed).collect();
+            elapsed.sort_by(f64::total_cmp);
+            let latency = (!elapsed.is_empty()).then(|| {
+                [
+                    percentile(&elapsed, 50.0),
+                    percentile(&elapsed, 90.0),
+                    percentile(&elapsed, 99.0),
+                    elapsed[elapsed.len() - 1],
+                ]
+            });
+
+            methods.push(MethodSummary {
+                method,
+                statuses,
+                latency,
+            });
+        }
+
+        let mut timestamps: Vec<SystemTime> = calls.iter().map(|call| call.timestamp).collect();
+        timestamps.sort();
+        let mut gaps: Vec<EngineGap> = timestamps
+            .windows(2)
+            .map(|pair| EngineGap {
+                start: pair[0],
+                end: pair[1],
+            })
+            .filter(|gap| gap.duration() > GAP_THRESHOLD)
+            .collect();
+        gaps.sort_by_key(|gap| std::cmp::Reverse(gap.duration()));
+
+        Some(EngineSummary { methods, gaps })
+    }
+
+    pub(crate) fn print<W: std::io::Write>(&self, writer: &mut W) {
+        writeln!(writer, "Engine API:").unwrap();
+        for summary in &self.methods {
+            let total: usize = summary.statuses.iter().map(|(_, count)| count).sum();
+            let statuses: Vec<String> = summary
+                .statuses
+                .iter()
+                .map(|(status, count)| {
+                    format!("{} {count}", status.map_or("UNKNOWN", PayloadStatus::name))
+                })
+                .collect();
+            let mut description = format!(
+                "  {}: {total} ({})",
+                summary.method.name(),
+                statuses.join(", ")
+            );
+            if let Some([p50, p90, p99, max]) = summary.latency {
+                description.push_str(&format!(
+                    ", latency p50 {}, p90 {}, p99 {}, max {}",
+                    format_elapsed(p50),
+                    format_elapsed(p90),
+                    format_elapsed(p99),
+                    format_elapsed(max)
+                ));
+            }
+            writeln!(writer, "{description}").unwrap();
+        }
+
+        if self.gaps.is_empty() {
+            return;
+        }
+        let total: Duration = self.gaps.iter().map(EngineGap::duration).sum();
+        writeln!(
+            writer,
+            "  {} gap{} without consensus layer messages longer than {}, {} in total:",
+            self.gaps.len(),
+            if self.gaps.len() == 1 { "" } else { "s" },
+            format_duration(&GAP_THRESHOLD),
+            format_duration(&total)
+        )
+        .unwrap();
+        for gap in self.gaps.iter().take(LONGEST_GAPS_LISTED) {
+            writeln!(
+                writer,
+                "    {} - {}: {}",
+                format_time(Some(gap.start)),
+                format_time(Some(gap.end)),
+                format_duration(&gap.duration())
+            )
+            .unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(
+        method: EngineMethod,
+        seconds: u64,
+        status: Option<PayloadStatus>,
+        hash: &str,
+    ) -> EngineCall {
+        EngineCall {
+            method,
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(1_718_000_000 + seconds),
+            status,
+            block: None,
+            hash: Some(hash.to_string()),
+            elapsed: None,
+        }
+    }
+
+    #[test]
+    fn test_engine_summary() {
+        let mut calls = Vec::new();
+        let new_payload = EngineMethod::NewPayload;
+        let forkchoice_updated = EngineMethod::ForkchoiceUpdated;
+        EngineCall::record(&mut calls, call(new_payload, 0, None, "0x1"));
+        EngineCall::record(
+            &mut calls,
+            call(forkchoice_updated, 1, Some(PayloadStatus::Valid), "0x1"),
+        );
+        EngineCall::record(&mut calls, call(new_payload, 12, None, "0x2"));
+        EngineCall::record(
+            &mut calls,
+            call(new_payload, 14, Some(PayloadStatus::Invalid), "0x2"),
+        );
+        EngineCall::record(
+            &mut calls,
+            call(forkchoice_updated, 120, Some(PayloadStatus::Syncing), "0x1"),
+        );
+        EngineCall::record(&mut calls, call(new_payload, 132, None, "0x3"));
+        assert!(EngineCall::resolve(
+            &mut calls,
+            Some("0x1"),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_718_000_001),
+            PayloadStatus::Valid
+        ));
+        assert!(!EngineCall::resolve(
+            &mut calls,
+            Some("0x4"),
+            SystemTime::UNIX_EPOCH,
+            PayloadStatus::Valid
+        ));
+        assert_eq!(calls.len(), 5);
+
+        let mut output = Vec::new();
+        EngineSummary::new(&calls).unwrap().print(&mut output);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Engine API:\n  \
+             forkchoiceUpdated: 2 (VALID 1, SYNCING 1)\n  \
+             newPayload: 3 (VALID 1, INVALID 1, UNKNOWN 1), latency p50 1.00s, p90 2.00s, p99 2.00s, max 2.00s\n  \
+             1 gap without consensus layer messages longer than 1m 0s, 1m 48s in total:\n    \
+             2024-06-10 06:13:32 - 2024-06-10 06:15:20: 1m 48s\n"
+        );
+        assert!(EngineSummary::new(&[]).is_none());
+    }
+}
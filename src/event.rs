@@ -0,0 +1,104 @@
+use crate::{
+    engine::EngineCall,
+    health::StatusSample,
+    live_sync::CanonicalBlock,
+    pipeline::{Interruption, PipelineStages, StageCheckpoint},
+};
+use std::time::{Duration, SystemTime};
+
+/// Progress of a stage as reported by reth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Progress {
+    /// Percentage of the blocks up to the target the stage went through.
+    pub(crate) percent: f64,
+    /// `None` when reth couldn't estimate it.
+    pub(crate) eta: Option<Duration>,
+}
+
+/// Progress of the running stage, as reported by a `Status` line.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StageStatus {
+    pub(crate) stage: String,
+    pub(crate) checkpoint: StageCheckpoint,
+    pub(crate) progress: Progress,
+}
+
+/// Something that happened in reth, extracted from a log line regardless of
+/// the format the line was written in.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Event {
+    StageStart {
+        stage: String,
+        timestamp: SystemTime,
+        checkpoint: StageCheckpoint,
+        /// `None` when the line doesn't tell.
+        pipeline_stages: Option<PipelineStages>,
+    },
+    /// The end of a run of a stage, which is `done` once it reached its
+    /// target, otherwise it committed its progress and runs again.
+    StageEnd {
+        stage: String,
+        timestamp: SystemTime,
+        checkpoint: StageCheckpoint,
+        done: bool,
+        progress: Option<Progress>,
+    },
+    /// A periodic `Status` line, which also reports the progress of the
+    /// running stage while the pipeline syncs.
+    Status {
+        sample: StatusSample,
+        stage: Option<StageStatus>,
+    },
+    /// A stage starting to unwind, from `checkpoint.checkpoint` down to
+    /// `checkpoint.target`.
+    UnwindStart {
+        stage: String,
+        timestamp: SystemTime,
+        checkpoint: StageCheckpoint,
+    },
+    /// A stage unwound down to `checkpoint.checkpoint`.
+    UnwindEnd {
+        stage: String,
+        timestamp: SystemTime,
+        checkpoint: StageCheckpoint,
+    },
+    /// A block added to the canonical chain once the node follows the tip.
+    BlockAdded { block: CanonicalBlock },
+    /// An Engine API call from the consensus layer, or the outcome of one.
+    EngineCall { call: EngineCall },
+    /// A state root validation, `elapsed` is in seconds.
+    StateRoot {
+        block: Option<u64>,
+        timestamp: SystemTime,
+        elapsed: f64,
+    },
+    /// reth started, as told by its startup banner.
+    Startup { timestamp: SystemTime },
+    /// reth terminated abnormally, as the `report` line tells. Reports don't
+    /// always carry a timestamp, and those of the `supervisor`, systemd or
+    /// the kernel, are written by another process than reth.
+    Abort {
+        timestamp: Option<SystemTime>,
+        reason: Interruption,
+        report: String,
+        supervisor: bool,
+    },
+}
+
+impl Event {
+    /// When the event happened, if known.
+    pub(crate) fn timestamp(&self) -> Option<SystemTime> {
+        match self {
+            Event::StageStart { timestamp, .. }
+            | Event::StageEnd { timestamp, .. }
+            | Event::UnwindStart { timestamp, .. }
+            | Event::UnwindEnd { timestamp, .. }
+            | Event::StateRoot { timestamp, .. }
+            | Event::Startup { timestamp } => Some(*timestamp),
+            Event::Status { sample, .. } => Some(sample.timestamp),
+            Event::BlockAdded { block } => Some(block.timestamp),
+            Event::EngineCall { call } => Some(call.timestamp),
+            Event::Abort { timestamp, .. } => *timestamp,
+        }
+    }
+}
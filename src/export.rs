@@ -0,0 +1,115 @@
+use crate::{
+    health::{HealthSummary, StatusSample},
+    log_processor::LogProcessor,
+    pipeline::Interruption,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use eyre::Result;
+use serde::Serialize;
+use std::{collections::BTreeMap, fs, path::Path, time::SystemTime};
+
+/// Summary of a parsed log, exported as JSON for other tools to consume.
+#[derive(Serialize)]
+struct Export<'a> {
+    pipelines: Vec<PipelineExport<'a>>,
+    sessions: Vec<SessionExport>,
+}
+
+#[derive(Serialize)]
+struct PipelineExport<'a> {
+    session: usize,
+    origin: Option<&'a str>,
+    /// Time spent in each stage in seconds, keyed by enumerated stage name.
+    stages: BTreeMap<&'a str, f64>,
+    health: Option<HealthSummary>,
+    status: Vec<StatusExport>,
+    abort: Option<AbortExport<'a>>,
+}
+
+/// How reth terminated abnormally, with its whole report.
+#[derive(Serialize)]
+struct AbortExport<'a> {
+    reason: Interruption,
+    timestamp: Option<String>,
+    stage: Option<&'a str>,
+    report: &'a str,
+    message: &'a [String],
+    backtrace: &'a [String],
+}
+
+#[derive(Serialize)]
+struct SessionExport {
+    pid: Option<u32>,
+    start: Option<String>,
+    end: Option<String>,
+    health: Option<HealthSummary>,
+    status: Vec<StatusExport>,
+}
+
+#[derive(Serialize)]
+struct StatusExport {
+    timestamp: String,
+    connected_peers: Option<u64>,
+    freelist: Option<u64>,
+    latest_block: Option<u64>,
+}
+
+/// Writes the stage durations and the node health series of the pipelines
+/// and sessions of `processor` to `path`.
+pub(crate) fn write(path: &Path, processor: &LogProcessor) -> Result<()> {
+    let export = Export {
+        pipelines: processor
+            .pipelines
+            .iter()
+            .map(|pipeline| PipelineExport {
+                session: pipeline.session,
+                origin: pipeline.origin.as_deref(),
+                stages: pipeline
+                    .durations
+                    .iter()
+                    .map(|(stage, duration)| (stage.as_str(), duration.as_secs_f64()))
+                    .collect(),
+                health: HealthSummary::new(&pipeline.status),
+                status: export_status(&pipeline.status),
+                abort: pipeline.abort.as_ref().map(|abort| AbortExport {
+                    reason: abort.reason,
+                    timestamp: abort.timestamp.map(format_timestamp),
+                    stage: abort.stage.as_deref(),
+                    report: &abort.report,
+                    message: &abort.message,
+                    backtrace: &abort.backtrace,
+                }),
+            })
+            .collect(),
+        sessions: processor
+            .sessions
+            .iter()
+            .map(|session| SessionExport {
+                pid: session.pid,
+                start: session.start.map(format_timestamp),
+                end: session.end.map(format_timestamp),
+                health: HealthSummary::new(&session.status),
+                status: export_status(&session.status),
+            })
+            .collect(),
+    };
+
+    fs::write(path, serde_json::to_vec_pretty(&export)?)?;
+    Ok(())
+}
+
+fn export_status(samples: &[StatusSample]) -> Vec<StatusExport> {
+    samples
+        .iter()
+        .map(|sample| StatusExport {
+            timestamp: format_timestamp(sample.timestamp),
+            connected_peers: sample.connected_peers,
+            freelist: sample.freelist,
+            latest_block: sample.latest_block,
+        })
+        .collect()
+}
+
+fn format_timestamp(timestamp: SystemTime) -> String {
+    DateTime::<Utc>::from(timestamp).to_rfc3339_opts(SecondsFormat::Micros, true)
+}
@@ -0,0 +1,552 @@
+use crate::{ansi::strip_ansi, input::Input, time::parse_rfc3339};
+use eyre::Result;
+use log::info;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+/// Largest binary field accepted from a journal export stream.
+const MAX_EXPORT_FIELD_LEN: usize = 64 * 1024 * 1024;
+
+/// Format of the records read from the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputFormat {
+    /// Detected from the first non empty line of the input.
+    #[default]
+    Auto,
+    /// Plain text lines, as written by reth or by `journalctl` in its default
+    /// short output.
+    Text,
+    /// One JSON object per line, as written by `journalctl -o json`.
+    JournaldJson,
+    /// The journal export format, as written by `journalctl -o export`.
+    JournaldExport,
+    /// Docker's `json-file` logging driver records, e.g.
+    /// `{"log":"...\n","stream":"stdout","time":"2024-06-07T09:05:20.873354123Z"}`.
+    Docker,
+    /// Kubernetes CRI container logs, e.g.
+    /// `2024-06-07T09:05:20.873354123Z stdout F ...`.
+    Cri,
+}
+
+impl InputFormat {
+    /// Parses the name used on the command line.
+    pub fn from_name(name: &str) -> Result<Self> {
+        Ok(match name {
+            "auto" => InputFormat::Auto,
+            "text" => InputFormat::Text,
+            "journald-json" => InputFormat::JournaldJson,
+            "journald-export" => InputFormat::JournaldExport,
+            "docker" => InputFormat::Docker,
+            "cri" => InputFormat::Cri,
+            _ => eyre::bail!("unknown input format {name}"),
+        })
+    }
+
+    /// Whether every entry is a single line.
+    pub(crate) fn is_line_oriented(self) -> bool {
+        matches!(self, InputFormat::Text | InputFormat::JournaldJson)
+    }
+
+    pub(crate) fn detect(line: &str) -> Self {
+        if line.starts_with('{') && line.contains("\"__REALTIME_TIMESTAMP\"") {
+            InputFormat::JournaldJson
+        } else if line.starts_with('{') && line.contains("\"log\"") {
+            InputFormat::Docker
+        } else if line.starts_with("__CURSOR=") || line.starts_with("__REALTIME_TIMESTAMP=") {
+            InputFormat::JournaldExport
+        } else if parse_cri(line).is_some() {
+            InputFormat::Cri
+        } else {
+            InputFormat::Text
+        }
+    }
+}
+
+/// Information about a log line provided by the log collector rather than by
+/// reth itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct EntryMetadata {
+    pub timestamp: Option<SystemTime>,
+    pub hostname: Option<String>,
+    pub pid: Option<u32>,
+    pub identifier: Option<String>,
+}
+
+impl EntryMetadata {
+    /// Describes the process that wrote the entry, in syslog style.
+    pub(crate) fn origin(&self) -> Option<String> {
+        let process = match (&self.identifier, self.pid) {
+            (Some(identifier), Some(pid)) => Some(format!("{identifier}[{pid}]")),
+            (Some(identifier), None) => Some(identifier.clone()),
+            (None, Some(pid)) => Some(format!("[{pid}]")),
+            (None, None) => None,
+        };
+
+        match (&self.hostname, process) {
+            (Some(hostname), Some(process)) => Some(format!("{hostname} {process}")),
+            (Some(hostname), None) => Some(hostname.clone()),
+            (None, process) => process,
+        }
+    }
+
+    fn from_journal_fields(fields: &HashMap<String, String>) -> Self {
+        EntryMetadata {
+            timestamp: fields
+                .get("__REALTIME_TIMESTAMP")
+                .and_then(|micros| micros.parse().ok())
+                .map(|micros| SystemTime::UNIX_EPOCH + Duration::from_micros(micros)),
+            hostname: fields.get("_HOSTNAME").cloned(),
+            pid: fields.get("_PID").and_then(|pid| pid.parse().ok()),
+            identifier: fields.get("SYSLOG_IDENTIFIER").cloned(),
+        }
+    }
+}
+
+/// A reth log line together with the metadata of the record it was read from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct LogEntry {
+    pub message: String,
+    pub metadata: EntryMetadata,
+}
+
+/// Turns the lines of an [`Input`] into [`LogEntry`]s according to the input
+/// format.
+pub(crate) struct EntryReader {
+    format: InputFormat,
+    /// Fields of the journal export record being read.
+    export_fields: HashMap<String, String>,
+    /// Start of a message container runtimes split across several records,
+    /// with the timestamp of its first fragment.
+    fragments: Option<(String, Option<SystemTime>)>,
+    ansi_cleaned_lines: usize,
+}
+
+impl EntryReader {
+    pub(crate) fn new(format: InputFormat) -> Self {
+        EntryReader {
+            format,
+            export_fields: HashMap::new(),
+            fragments: None,
+            ansi_cleaned_lines: 0,
+        }
+    }
+
+    /// Reads the next entry, `Some(None)` for records which don't hold one.
+    pub(crate) fn next_entry(&mut self, input: &mut Input) -> Result<Option<Option<LogEntry>>> {
+        let entry = self.read_entry(input)?;
+        Ok(entry.map(|entry| entry.map(|entry| self.clean(entry))))
+    }
+
+    /// Turns a single line into an entry, for line oriented formats.
+    pub(crate) fn entry_from_line(&mut self, line: String) -> Option<LogEntry> {
+        debug_assert!(self.format.is_line_oriented());
+        self.parse_line(line).map(|entry| self.clean(entry))
+    }
+
+    fn clean(&mut self, mut entry: LogEntry) -> LogEntry {
+        if let Some(message) = strip_ansi(&entry.message) {
+            entry.message = message;
+            self.ansi_cleaned_lines += 1;
+        }
+        entry
+    }
+
+    /// Number of entries ANSI escape sequences were removed from.
+    pub(crate) fn ansi_cleaned_lines(&self) -> usize {
+        self.ansi_cleaned_lines
+    }
+
+    fn read_entry(&mut self, input: &mut Input) -> Result<Option<Option<LogEntry>>> {
+        if self.format == InputFormat::JournaldExport {
+            return self.next_export_entry(input);
+        }
+
+        let Some(line) = input.next_line()? else {
+            return Ok(None);
+        };
+        let Some(line) = line else {
+            return Ok(Some(None));
+        };
+
+        if self.format == InputFormat::Auto {
+            if line.trim().is_empty() {
+                return Ok(Some(None));
+            }
+            self.format = InputFormat::detect(&line);
+            info!("Reading {:?} input", self.format);
+            if self.format == InputFormat::JournaldExport {
+                self.add_export_field(&line, input)?;
+                return self.next_export_entry(input);
+            }
+        }
+
+        Ok(Some(self.parse_line(line)))
+    }
+
+    fn parse_line(&mut self, line: String) -> Option<LogEntry> {
+        match self.format {
+            InputFormat::JournaldJson => parse_journald_json(&line),
+            InputFormat::Docker => self.parse_docker(&line),
+            InputFormat::Cri => parse_cri(&line).and_then(|(timestamp, complete, message)| {
+                self.join_fragments(message, complete, timestamp)
+            }),
+            _ => Some(LogEntry {
+                message: line,
+                metadata: EntryMetadata::default(),
+            }),
+        }
+    }
+
+    fn next_export_entry(&mut self, input: &mut Input) -> Result<Option<Option<LogEntry>>> {
+        loop {
+            match input.next_line()? {
+                None if self.export_fields.is_empty() || input.is_following() => return Ok(None),
+                // The last record isn't always followed by an empty line.
+                None => break,
+                Some(Some(line)) if line.is_empty() => {
+                    if !self.export_fields.is_empty() {
+                        break;
+                    }
+                }
+                Some(Some(line)) => self.add_export_field(&line, input)?,
+                // Text fields are always valid UTF-8, anything else means the
+                // field is corrupt, its value is left out of the record.
+                Some(None) => {}
+            }
+        }
+
+        let fields = std::mem::take(&mut self.export_fields);
+        Ok(Some(journal_entry(fields)))
+    }
+
+    /// Adds a `FIELD=value` line, or a binary field named on `line`, to the
+    /// record.
+    fn add_export_field(&mut self, line: &str, input: &mut Input) -> Result<()> {
+        if let Some((name, value)) = line.split_once('=') {
+            self.export_fields
+                .insert(name.to_string(), value.to_string());
+        } else {
+            let mut len = [0; 8];
+            input.read_exact(&mut len)?;
+            let len = u64::from_le_bytes(len);
+            let size = usize::try_from(len)
+                .ok()
+                .filter(|&len| len <= MAX_EXPORT_FIELD_LEN)
+                .and_then(|len| len.checked_add(1))
+                .ok_or_else(|| eyre::eyre!("binary field {line} is too long: {len} bytes"))?;
+            let mut value = vec![0; size];
+            input.read_exact(&mut value)?;
+            value.pop();
+            self.export_fields.insert(
+                line.to_string(),
+                String::from_utf8_lossy(&value).into_owned(),
+            );
+        }
+        Ok(())
+    }
+
+    fn parse_docker(&mut self, line: &str) -> Option<LogEntry> {
+        let Ok(Value::Object(object)) = serde_json::from_str::<Value>(line) else {
+            return None;
+        };
+        let log = object.get("log")?.as_str()?;
+        let timestamp = object
+            .get("time")
+            .and_then(Value::as_str)
+            .and_then(parse_rfc3339);
+
+        // Docker splits long lines into several records, only the last one
+        // keeps the newline.
+        match log.strip_suffix('\n') {
+            Some(message) => self.join_fragments(message, true, timestamp),
+            None => self.join_fragments(log, false, timestamp),
+        }
+    }
+
+    /// Joins the fragments of a message split by the container runtime,
+    /// returning the message once its last fragment has been read.
+    fn join_fragments(
+        &mut self,
+        fragment: &str,
+        complete: bool,
+        timestamp: Option<SystemTime>,
+    ) -> Option<LogEntry> {
+        let (message, timestamp) = match self.fragments.take() {
+            Some((mut message, first_timestamp)) => {
+                message.push_str(fragment);
+                (message, first_timestamp.or(timestamp))
+            }
+            None => (fragment.to_string(), timestamp),
+        };
+
+        if !complete {
+            self.fragments = Some((message, timestamp));
+            return None;
+        }
+
+        Some(LogEntry {
+            message: message.trim_end_matches('\r').to_string(),
+            metadata: EntryMetadata {
+                timestamp,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// Splits a CRI log line into its timestamp, whether it is the last fragment
+/// of the message (`F` rather than `P` tag) and the message itself.
+fn parse_cri(line: &str) -> Option<(Option<SystemTime>, bool, &str)> {
+    let mut parts = line.splitn(4, ' ');
+    let timestamp = parse_rfc3339(parts.next()?)?;
+    if !matches!(parts.next()?, "stdout" | "stderr") {
+        return None;
+    }
+    let complete = match parts.next()?.split(':').next()? {
+        "F" => true,
+        "P" => false,
+        _ => return None,
+    };
+
+    Some((Some(timestamp), complete, parts.next().unwrap_or_default()))
+}
+
+fn parse_journald_json(line: &str) -> Option<LogEntry> {
+    let Ok(Value::Object(object)) = serde_json::from_str::<Value>(line) else {
+        return None;
+    };
+
+    let fields = object
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let value = match value {
+                Value::String(value) => value,
+                // Fields which aren't valid UTF-8 are exported as byte arrays.
+                Value::Array(bytes) => {
+                    let bytes: Vec<u8> = bytes
+                        .iter()
+                        .filter_map(|byte| byte.as_u64().map(|byte| byte as u8))
+                        .collect();
+                    String::from_utf8_lossy(&bytes).into_owned()
+                }
+                _ => return None,
+            };
+            Some((name, value))
+        })
+        .collect();
+
+    journal_entry(fields)
+}
+
+fn journal_entry(mut fields: HashMap<String, String>) -> Option<LogEntry> {
+    let message = fields.remove("MESSAGE")?;
+    Some(LogEntry {
+        message,
+        metadata: EntryMetadata::from_journal_fields(&fields),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::LogSource;
+    use std::io::Cursor;
+
+    fn read_entries(format: InputFormat, data: &[u8]) -> Vec<Option<LogEntry>> {
+        let mut input = LogSource::Reader(Box::new(Cursor::new(data.to_vec())))
+            .open()
+            .unwrap();
+        let mut reader = EntryReader::new(format);
+        let mut entries = Vec::new();
+        while let Some(entry) = reader.next_entry(&mut input).unwrap() {
+            entries.push(entry);
+        }
+        entries
+    }
+
+    fn reth_entry(message: &str) -> LogEntry {
+        LogEntry {
+            message: message.to_string(),
+            metadata: EntryMetadata {
+                timestamp: Some(SystemTime::UNIX_EPOCH + Duration::from_micros(1717533881293617)),
+                hostname: Some("reth-5".to_string()),
+                pid: Some(4009735),
+                identifier: Some("reth-mainnet".to_string()),
+            },
+        }
+    }
+
+    #[test]
+    fn test_journald_json() {
+        let data = br#"{"__REALTIME_TIMESTAMP":"1717533881293617","_HOSTNAME":"reth-5","_PID":"4009735","SYSLOG_IDENTIFIER":"reth-mainnet","MESSAGE":"INFO Preparing stage"}
+{"__REALTIME_TIMESTAMP":"1717533881293617","_HOSTNAME":"reth-5","_PID":"4009735","SYSLOG_IDENTIFIER":"reth-mainnet","MESSAGE":[73,78,70,79,32,255]}
+{"__REALTIME_TIMESTAMP":"1717533881293617","MESSAGE":null}
+not json
+"#;
+
+        assert_eq!(
+            read_entries(InputFormat::Auto, data),
+            vec![
+                Some(reth_entry("INFO Preparing stage")),
+                Some(reth_entry("INFO \u{FFFD}")),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_journald_export() {
+        let mut data = b"__CURSOR=s=1\n__REALTIME_TIMESTAMP=1717533881293617\n_HOSTNAME=reth-5\n_PID=4009735\nSYSLOG_IDENTIFIER=reth-mainnet\nMESSAGE=INFO Preparing stage\n\n".to_vec();
+        data.extend(b"__REALTIME_TIMESTAMP=1717533881293617\n_HOSTNAME=reth-5\n_PID=4009735\nSYSLOG_IDENTIFIER=reth-mainnet\nMESSAGE\n");
+        data.extend(15u64.to_le_bytes());
+        data.extend(b"INFO multi\nline\n");
+        data.extend(b"\n__REALTIME_TIMESTAMP=1717533881293617\n");
+
+        assert_eq!(
+            read_entries(InputFormat::Auto, &data),
+            vec![
+                Some(reth_entry("INFO Preparing stage")),
+                Some(reth_entry("INFO multi\nline")),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_journald_export_bogus_length() {
+        let mut data = b"__REALTIME_TIMESTAMP=1717533881293617\nMESSAGE\n".to_vec();
+        data.extend(u64::MAX.to_le_bytes());
+        data.extend(b"INFO short\n");
+        let mut input = LogSource::Reader(Box::new(Cursor::new(data)))
+            .open()
+            .unwrap();
+        let mut reader = EntryReader::new(InputFormat::JournaldExport);
+
+        let err = reader.next_entry(&mut input).unwrap_err();
+        assert!(err.to_string().contains("too long"), "{err}");
+    }
+
+    #[test]
+    fn test_text() {
+        let entries = read_entries(InputFormat::Auto, b"\nINFO Preparing stage\n");
+
+        assert_eq!(
+            entries,
+            vec![
+                None,
+                Some(LogEntry {
+                    message: "INFO Preparing stage".to_string(),
+                    metadata: EntryMetadata::default(),
+                })
+            ]
+        );
+    }
+
+    fn container_entry(message: &str, timestamp: &str) -> LogEntry {
+        LogEntry {
+            message: message.to_string(),
+            metadata: EntryMetadata {
+                timestamp: parse_rfc3339(timestamp),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_docker() {
+        let data = br#"{"log":"2024-06-07T09:05:20.873354Z  INFO Preparing stage\n","stream":"stdout","time":"2024-06-07T09:05:20.873400123Z"}
+{"log":"INFO long ","stream":"stdout","time":"2024-06-07T09:05:21.000000001Z"}
+{"log":"line\r\n","stream":"stdout","time":"2024-06-07T09:05:21.000000002Z"}
+{"stream":"stdout"}
+"#;
+
+        assert_eq!(
+            read_entries(InputFormat::Auto, data),
+            vec![
+                Some(container_entry(
+                    "2024-06-07T09:05:20.873354Z  INFO Preparing stage",
+                    "2024-06-07T09:05:20.873400123Z"
+                )),
+                None,
+                Some(container_entry(
+                    "INFO long line",
+                    "2024-06-07T09:05:21.000000001Z"
+                )),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cri() {
+        let data = b"2024-06-07T09:05:20.873400123Z stdout F 2024-06-07T09:05:20.873354Z  INFO Preparing stage
+2024-06-07T09:05:21.000000001Z stderr P INFO long 
+2024-06-07T09:05:21.000000002Z stderr F line
+2024-06-07T09:05:21.000000003Z stdout F
+not a cri line
+";
+
+        assert_eq!(
+            read_entries(InputFormat::Auto, data),
+            vec![
+                Some(container_entry(
+                    "2024-06-07T09:05:20.873354Z  INFO Preparing stage",
+                    "2024-06-07T09:05:20.873400123Z"
+                )),
+                None,
+                Some(container_entry(
+                    "INFO long line",
+                    "2024-06-07T09:05:21.000000001Z"
+                )),
+                Some(container_entry("", "2024-06-07T09:05:21.000000003Z")),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            InputFormat::detect("2024-06-07T09:05:20.873354Z  INFO Preparing stage"),
+            InputFormat::Text
+        );
+        assert_eq!(
+            InputFormat::detect("2024-06-07T09:05:20.873400123Z stdout F 2024-06-07T09:05:20.873354Z  INFO Preparing stage"),
+            InputFormat::Cri
+        );
+        assert_eq!(
+            InputFormat::detect(
+                r#"{"timestamp":"2024-06-07T09:05:20.873354Z","level":"INFO","fields":{"message":"Preparing stage"},"target":"reth::cli"}"#
+            ),
+            InputFormat::Text
+        );
+    }
+
+    #[test]
+    fn test_strips_ansi() {
+        let data = b"\x1b[32m INFO\x1b[0m Preparing stage\n INFO Finished stage\n";
+        let mut input = LogSource::Reader(Box::new(Cursor::new(data.to_vec())))
+            .open()
+            .unwrap();
+        let mut reader = EntryReader::new(InputFormat::Text);
+
+        let entry = reader.next_entry(&mut input).unwrap().unwrap().unwrap();
+        assert_eq!(entry.message, " INFO Preparing stage");
+        let entry = reader.next_entry(&mut input).unwrap().unwrap().unwrap();
+        assert_eq!(entry.message, " INFO Finished stage");
+        assert_eq!(reader.ansi_cleaned_lines(), 1);
+    }
+
+    #[test]
+    fn test_origin() {
+        assert_eq!(
+            reth_entry("").metadata.origin(),
+            Some("reth-5 reth-mainnet[4009735]".to_string())
+        );
+        assert_eq!(EntryMetadata::default().origin(), None);
+    }
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// Health of the node as reported by a periodic `Status` line, e.g.
+/// `Status connected_peers=132 freelist=748277 latest_block=20110636`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusSample {
+    pub timestamp: SystemTime,
+    pub connected_peers: Option<u64>,
+    /// Pages of the database free list.
+    pub freelist: Option<u64>,
+    /// Latest block, only logged once the pipeline is done syncing.
+    pub latest_block: Option<u64>,
+}
+
+/// Summary of the `Status` lines logged over a pipeline or a session.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HealthSummary {
+    pub samples: usize,
+    pub min_peers: Option<u64>,
+    pub mean_peers: Option<f64>,
+    pub max_peers: Option<u64>,
+    /// Free list size in the first and the last sample reporting it.
+    pub freelist: Option<(u64, u64)>,
+    /// Latest block in the first and the last sample reporting it.
+    pub latest_block: Option<(u64, u64)>,
+    /// Blocks the latest block advanced by per second between them.
+    pub blocks_per_second: Option<f64>,
+}
+
+impl HealthSummary {
+    /// Summarises samples in the order they were logged, `None` if there are
+    /// none.
+    pub fn new(samples: &[StatusSample]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let peers: Vec<u64> = samples
+            .iter()
+            .filter_map(|sample| sample.connected_peers)
+            .collect();
+        let first_and_last = |value: fn(&StatusSample) -> Option<u64>| {
+            let first = samples.iter().find_map(value)?;
+            let last = samples.iter().rev().find_map(value)?;
+            Some((first, last))
+        };
+
+        let latest_blocks: Vec<(SystemTime, u64)> = samples
+            .iter()
+            .filter_map(|sample| Some((sample.timestamp, sample.latest_block?)))
+            .collect();
+        let blocks_per_second = match (latest_blocks.first(), latest_blocks.last()) {
+            (Some((first_time, first)), Some((last_time, last))) => last_time
+                .duration_since(*first_time)
+                .ok()
+                .filter(|elapsed| !elapsed.is_zero())
+                .map(|elapsed| (*last as f64 - *first as f64) / elapsed.as_secs_f64()),
+            _ => None,
+        };
+
+        Some(HealthSummary {
+            samples: samples.len(),
+            min_peers: peers.iter().min().copied(),
+            mean_peers: (!peers.is_empty())
+                .then(|| peers.iter().sum::<u64>() as f64 / peers.len() as f64),
+            max_peers: peers.iter().max().copied(),
+            freelist: first_and_last(|sample| sample.freelist),
+            latest_block: first_and_last(|sample| sample.latest_block),
+            blocks_per_second,
+        })
+    }
+
+    /// Describes the summary on one line, e.g. `3 status reports, peers 10-132
+    /// (91.0 on average), freelist 4 -> 748277 (+748273), latest block 0 ->
+    /// 20110636 (3693.2 blocks/s)`.
+    pub fn describe(&self) -> String {
+        let mut description = format!(
+            "{} status report{}",
+            self.samples,
+            if self.samples == 1 { "" } else { "s" }
+        );
+        if let (Some(min), Some(mean), Some(max)) =
+            (self.min_peers, self.mean_peers, self.max_peers)
+        {
+            description.push_str(&format!(", peers {min}-{max} ({mean:.1} on average)"));
+        }
+        if let Some((first, last)) = self.freelist {
+            let growth = last as i128 - first as i128;
+            description.push_str(&format!(", freelist {first} -> {last} ({growth:+})"));
+        }
+        if let Some((first, last)) = self.latest_block {
+            description.push_str(&format!(", latest block {first} -> {last}"));
+            if let Some(rate) = self.blocks_per_second {
+                description.push_str(&format!(" ({rate:.1} blocks/s)"));
+            }
+        }
+        description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_health_summary() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_718_000_000);
+        let samples = [
+            StatusSample {
+                timestamp: start,
+                connected_peers: Some(10),
+                freelist: Some(4),
+                latest_block: None,
+            },
+            StatusSample {
+                timestamp: start + Duration::from_secs(100),
+                connected_peers: Some(131),
+                freelist: Some(2425505),
+                latest_block: Some(1000),
+            },
+            StatusSample {
+                timestamp: start + Duration::from_secs(200),
+                connected_peers: Some(132),
+                freelist: Some(748277),
+                latest_block: Some(1500),
+            },
+        ];
+
+        let summary = HealthSummary::new(&samples).unwrap();
+        assert_eq!(summary.min_peers, Some(10));
+        assert_eq!(summary.max_peers, Some(132));
+        assert_eq!(summary.freelist, Some((4, 748277)));
+        assert_eq!(summary.blocks_per_second, Some(5.0));
+        assert_eq!(
+            summary.describe(),
+            "3 status reports, peers 10-132 (91.0 on average), freelist 4 -> 748277 (+748273), \
+             latest block 1000 -> 1500 (5.0 blocks/s)"
+        );
+        assert!(HealthSummary::new(&[]).is_none());
+    }
+}
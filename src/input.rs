@@ -0,0 +1,824 @@
+use crate::{format::EntryMetadata, time::TimestampExtractor};
+use eyre::Result;
+use flate2::read::MultiGzDecoder;
+use log::{info, warn};
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+/// Number of lines read from the start of a rotated file looking for the
+/// timestamp it should be ordered by.
+const FIRST_TIMESTAMP_PROBE_LINES: usize = 1000;
+
+/// Where the runner reads reth logs from.
+pub enum LogSource {
+    /// A path on disk, either a regular file or a stream like a named pipe.
+    File(PathBuf),
+    /// A logs directory, whose rotated log files are read as one set.
+    Directory(PathBuf),
+    /// A glob pattern matching the files of a rotated set, e.g. `logs/reth.log*`.
+    Glob(String),
+    /// The process standard input, selected with `-` on the command line.
+    Stdin,
+    /// Any other reader provided by the caller.
+    Reader(Box<dyn Read>),
+}
+
+impl LogSource {
+    /// Builds a source from a command line argument, `-` meaning stdin.
+    pub fn from_arg(arg: &str) -> Self {
+        if arg == "-" {
+            LogSource::Stdin
+        } else if Path::new(arg).is_dir() {
+            LogSource::Directory(PathBuf::from(arg))
+        } else if arg.contains(['*', '?', '[']) {
+            LogSource::Glob(arg.to_string())
+        } else {
+            LogSource::File(PathBuf::from(arg))
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn open(self) -> Result<Input> {
+        self.open_at(0)
+    }
+
+    /// Opens the source skipping its first `offset` bytes, for files only.
+    pub(crate) fn open_at(self, offset: u64) -> Result<Input> {
+        let resumable = matches!(self, LogSource::File(_));
+        if offset > 0 && !resumable {
+            eyre::bail!("only log files can be read from an offset");
+        }
+
+        let bytes_read = Rc::new(Cell::new(offset));
+        let (segments, total_bytes) = match self {
+            LogSource::File(path) => {
+                let mut file = File::open(&path)?;
+                let metadata = file.metadata()?;
+                let total_bytes = metadata.is_file().then_some(metadata.len());
+                file.seek(SeekFrom::Start(offset))?;
+                let segment = Segment::new(Box::new(file), &bytes_read, Some(&path))?;
+                (vec![segment], total_bytes)
+            }
+            LogSource::Directory(path) => {
+                let mut logs = Vec::new();
+                for entry in fs::read_dir(path)? {
+                    let entry = entry?;
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    if !entry.file_type()?.is_file() || name.starts_with('.') {
+                        continue;
+                    }
+                    if let Some(base) = rotation_base(&name) {
+                        logs.push((base.to_string(), entry.path()));
+                    }
+                }
+                open_rotated_set(rotated_set(logs)?, &bytes_read)?
+            }
+            LogSource::Glob(pattern) => {
+                let paths = glob::glob(&pattern)?
+                    .filter_map(|path| path.ok())
+                    .filter(|path| path.is_file())
+                    .collect();
+                open_rotated_set(paths, &bytes_read)?
+            }
+            LogSource::Stdin => (
+                vec![Segment::new(Box::new(io::stdin()), &bytes_read, None)?],
+                None,
+            ),
+            LogSource::Reader(reader) => (vec![Segment::new(reader, &bytes_read, None)?], None),
+        };
+
+        Ok(Input {
+            segments: segments.into(),
+            bytes_read,
+            total_bytes,
+            offset,
+            resumable,
+            follow: None,
+            partial: false,
+            buf: Vec::new(),
+        })
+    }
+}
+
+/// Base name of a log file or of one of its rotations, e.g. `reth.log` for
+/// `reth.log.1.gz`, `reth.log.2024-06-07` or `reth.log-20240607`.
+fn rotation_base(name: &str) -> Option<&str> {
+    let end = name.find(".log")? + ".log".len();
+    let (base, suffix) = name.split_at(end);
+    let is_rotation = |part: &str| {
+        matches!(part, "gz" | "zst" | "xz")
+            || (!part.is_empty()
+                && part
+                    .bytes()
+                    .all(|byte| byte.is_ascii_digit() || byte == b'-'))
+    };
+    let rotated = suffix.starts_with(['.', '-']) && suffix[1..].split('.').all(is_rotation);
+    (suffix.is_empty() || rotated).then_some(base)
+}
+
+/// Picks the files of the rotated set of `reth.log`, or of the only base name.
+fn rotated_set(logs: Vec<(String, PathBuf)>) -> Result<Vec<PathBuf>> {
+    let mut bases: Vec<&str> = logs.iter().map(|(base, _)| base.as_str()).collect();
+    bases.sort();
+    bases.dedup();
+    let base = match bases.as_slice() {
+        [] => eyre::bail!("no log files found"),
+        [base] => base.to_string(),
+        _ if bases.contains(&"reth.log") => "reth.log".to_string(),
+        _ => eyre::bail!(
+            "several logs found ({}), pass a glob to pick one",
+            bases.join(", ")
+        ),
+    };
+    Ok(logs
+        .into_iter()
+        .filter(|(other, _)| *other == base)
+        .map(|(_, path)| path)
+        .collect())
+}
+
+/// Opens the files of a rotated set ordered by the first timestamp in each.
+fn open_rotated_set(
+    mut paths: Vec<PathBuf>,
+    bytes_read: &Rc<Cell<u64>>,
+) -> Result<(Vec<Segment>, Option<u64>)> {
+    if paths.is_empty() {
+        eyre::bail!("no log files found");
+    }
+
+    paths.sort();
+    let mut ordered = Vec::with_capacity(paths.len());
+    for path in paths {
+        let first_timestamp = first_timestamp(&path, &mut TimestampExtractor::new()?)?;
+        if first_timestamp.is_none() {
+            warn!("No timestamp found in {}", path.display());
+        }
+        ordered.push((first_timestamp.is_none(), first_timestamp, path));
+    }
+    ordered.sort();
+
+    let mut segments = Vec::with_capacity(ordered.len());
+    let mut total_bytes = 0;
+    for (_, _, path) in ordered {
+        let file = File::open(&path)?;
+        total_bytes += file.metadata()?.len();
+        segments.push(Segment::new(Box::new(file), bytes_read, Some(&path))?);
+    }
+    info!("Reading {} rotated log files", segments.len());
+
+    Ok((segments, Some(total_bytes)))
+}
+
+fn first_timestamp(path: &Path, timestamps: &mut TimestampExtractor) -> Result<Option<SystemTime>> {
+    let raw: Box<dyn Read> = Box::new(File::open(path)?);
+    let (compression, raw) = Compression::sniff(raw)?;
+    let reader = BufReader::new(compression.decoder(raw)?);
+
+    Ok(reader
+        .lines()
+        .take(FIRST_TIMESTAMP_PROBE_LINES)
+        .map_while(|line| line.ok())
+        .find_map(|line| timestamps.extract(&line, &EntryMetadata::default()).ok()))
+}
+
+/// Compression formats recognised by their magic bytes, as produced by log
+/// rotation tools such as logrotate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+/// Whether `data` starts with the magic bytes of a supported compression format.
+pub(crate) fn is_compressed(data: &[u8]) -> bool {
+    Compression::detect(data) != Compression::None
+}
+
+impl Compression {
+    const MAGIC_LEN: usize = 6;
+
+    fn detect(magic: &[u8]) -> Self {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Detects the compression of `reader`, returned with its magic bytes.
+    fn sniff(mut reader: Box<dyn Read>) -> Result<(Self, Box<dyn Read>)> {
+        let mut magic = Vec::with_capacity(Self::MAGIC_LEN);
+        (&mut reader)
+            .take(Self::MAGIC_LEN as u64)
+            .read_to_end(&mut magic)?;
+
+        Ok((
+            Self::detect(&magic),
+            Box::new(Cursor::new(magic).chain(reader)),
+        ))
+    }
+
+    /// Wraps `reader` with the matching streaming decoder.
+    fn decoder(self, reader: Box<dyn Read>) -> Result<Box<dyn Read>> {
+        Ok(match self {
+            Compression::None => reader,
+            Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        })
+    }
+}
+
+/// One decompressed stream of an [`Input`], per file of a rotated set.
+struct Segment {
+    reader: Box<dyn BufRead>,
+    path: Option<PathBuf>,
+    compression: Compression,
+}
+
+impl Segment {
+    fn new(reader: Box<dyn Read>, bytes_read: &Rc<Cell<u64>>, path: Option<&Path>) -> Result<Self> {
+        let counting = CountingReader {
+            inner: reader,
+            count: bytes_read.clone(),
+        };
+        let (compression, raw) = Compression::sniff(Box::new(counting))?;
+        if compression != Compression::None {
+            match path {
+                Some(path) => info!("Decompressing {compression:?} file {}", path.display()),
+                None => info!("Decompressing {compression:?} input"),
+            }
+        }
+
+        Ok(Segment {
+            reader: Box::new(BufReader::new(compression.decoder(raw)?)),
+            path: path.map(Path::to_path_buf),
+            compression,
+        })
+    }
+}
+
+/// State of the file being followed once the input has been read to its end.
+struct Follow {
+    path: PathBuf,
+    id: Option<FileId>,
+}
+
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<FileId> {
+    None
+}
+
+/// A line oriented view over a [`LogSource`] counting the raw bytes consumed.
+pub(crate) struct Input {
+    segments: VecDeque<Segment>,
+    bytes_read: Rc<Cell<u64>>,
+    total_bytes: Option<u64>,
+    /// Offset in the current segment up to which data has been read.
+    offset: u64,
+    /// Whether the input is a single file, which can be resumed from `offset`.
+    resumable: bool,
+    follow: Option<Follow>,
+    /// Whether `buf` holds the start of a line which hasn't been fully written
+    /// to the followed file yet.
+    partial: bool,
+    buf: Vec<u8>,
+}
+
+impl Input {
+    /// Keeps the last file of the input open once its end is reached.
+    pub(crate) fn follow(&mut self) -> Result<()> {
+        let segment = self
+            .segments
+            .back()
+            .ok_or_else(|| eyre::eyre!("nothing to follow"))?;
+        let path = segment
+            .path
+            .clone()
+            .ok_or_else(|| eyre::eyre!("only log files can be followed"))?;
+        if segment.compression != Compression::None {
+            eyre::bail!("compressed file {} can't be followed", path.display());
+        }
+
+        self.follow = Some(Follow {
+            id: file_id(&fs::metadata(&path)?),
+            path,
+        });
+        self.total_bytes = None;
+        Ok(())
+    }
+
+    /// Reads the next line without its trailing newline, `Some(None)` when it
+    /// isn't valid UTF-8.
+    pub(crate) fn next_line(&mut self) -> io::Result<Option<Option<String>>> {
+        if !self.partial {
+            self.buf.clear();
+        }
+        loop {
+            let last = self.segments.len() == 1;
+            let Some(segment) = self.segments.front_mut() else {
+                return Ok(None);
+            };
+            let read = segment.reader.read_until(b'\n', &mut self.buf)?;
+            self.offset += read as u64;
+            if read == 0 && !last {
+                self.segments.pop_front();
+                self.offset = 0;
+                // A file rotated away while its last line was being written
+                // still ends that line.
+                if self.buf.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            if last && self.follow.is_some() {
+                // Lines are only complete once their newline has been written.
+                self.partial = self.buf.last() != Some(&b'\n');
+                if self.partial {
+                    return Ok(None);
+                }
+            }
+            if self.buf.is_empty() {
+                return Ok(None);
+            }
+            break;
+        }
+        self.partial = false;
+
+        if self.buf.last() == Some(&b'\n') {
+            self.buf.pop();
+            if self.buf.last() == Some(&b'\r') {
+                self.buf.pop();
+            }
+        }
+
+        Ok(Some(String::from_utf8(self.buf.clone()).ok()))
+    }
+
+    /// Reads raw bytes following the last line returned, used for the binary
+    /// fields of the journal export format.
+    pub(crate) fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let segment = self
+            .segments
+            .front_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        segment.reader.read_exact(buf)?;
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Offset of an uncompressed file up to which complete lines were read.
+    pub(crate) fn resume_offset(&self) -> Option<u64> {
+        let segment = self.segments.front()?;
+        if !self.resumable || segment.compression != Compression::None {
+            return None;
+        }
+        let partial = if self.partial { self.buf.len() } else { 0 };
+        Some(self.offset - partial as u64)
+    }
+
+    pub(crate) fn is_following(&self) -> bool {
+        self.follow.is_some()
+    }
+
+    pub(crate) fn bytes_read(&self) -> u64 {
+        self.bytes_read.get()
+    }
+
+    pub(crate) fn total_bytes(&self) -> Option<u64> {
+        self.total_bytes
+    }
+
+    /// Reopens the followed file if it was rotated away or truncated.
+    pub(crate) fn reopen_if_replaced(&mut self) -> Result<()> {
+        let Some(follow) = &mut self.follow else {
+            return Ok(());
+        };
+        let metadata = match fs::metadata(&follow.path) {
+            Ok(metadata) => metadata,
+            // The file was moved away and its replacement isn't there yet.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let id = file_id(&metadata);
+        if id != follow.id {
+            info!("{} was rotated, reopening it", follow.path.display());
+            let file = File::open(&follow.path)?;
+            let segment = Segment::new(Box::new(file), &self.bytes_read, Some(&follow.path))?;
+            follow.id = id;
+            // The rotated file stays open until its remaining lines are read.
+            self.segments.push_back(segment);
+        } else if self.segments.len() == 1 && metadata.len() < self.offset {
+            info!("{} was truncated, reading it again", follow.path.display());
+            let file = File::open(&follow.path)?;
+            let segment = Segment::new(Box::new(file), &self.bytes_read, Some(&follow.path))?;
+            self.offset = 0;
+            self.segments = VecDeque::from([segment]);
+            self.partial = false;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
+
+struct CountingReader<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression as GzLevel};
+    use rstest::rstest;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    const LINES: &str = "first\nsecond\n";
+
+    fn compress(compression: Compression, data: &[u8]) -> Vec<u8> {
+        match compression {
+            Compression::None => data.to_vec(),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), GzLevel::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Zstd => zstd::encode_all(data, 0).unwrap(),
+            Compression::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_arg() {
+        assert!(matches!(LogSource::from_arg("-"), LogSource::Stdin));
+        assert!(
+            matches!(LogSource::from_arg("reth.log"), LogSource::File(path) if path == PathBuf::from("reth.log"))
+        );
+    }
+
+    #[test]
+    fn test_input_from_file_knows_total_bytes() {
+        let mut log_file = NamedTempFile::new().unwrap();
+        writeln!(log_file, "first").unwrap();
+        writeln!(log_file, "second").unwrap();
+
+        let mut input = LogSource::File(log_file.path().to_path_buf())
+            .open()
+            .unwrap();
+
+        assert_eq!(input.total_bytes(), Some(13));
+        assert_eq!(input.next_line().unwrap(), Some(Some("first".to_string())));
+        assert_eq!(input.next_line().unwrap(), Some(Some("second".to_string())));
+        assert_eq!(input.next_line().unwrap(), None);
+        assert_eq!(input.bytes_read(), 13);
+    }
+
+    #[test]
+    fn test_input_from_reader() {
+        let reader = Cursor::new(b"first\r\n\xff\xfe\nlast".to_vec());
+        let mut input = LogSource::Reader(Box::new(reader)).open().unwrap();
+
+        assert_eq!(input.total_bytes(), None);
+        assert_eq!(input.next_line().unwrap(), Some(Some("first".to_string())));
+        assert_eq!(input.next_line().unwrap(), Some(None));
+        assert_eq!(input.next_line().unwrap(), Some(Some("last".to_string())));
+        assert_eq!(input.next_line().unwrap(), None);
+        assert_eq!(input.bytes_read(), 14);
+    }
+
+    #[rstest]
+    #[case(Compression::None)]
+    #[case(Compression::Gzip)]
+    #[case(Compression::Zstd)]
+    #[case(Compression::Xz)]
+    fn test_input_decompresses(#[case] compression: Compression) {
+        let mut log_file = NamedTempFile::new().unwrap();
+        let compressed = compress(compression, LINES.as_bytes());
+        log_file.write_all(&compressed).unwrap();
+
+        let mut input = LogSource::File(log_file.path().to_path_buf())
+            .open()
+            .unwrap();
+
+        let (detected, _) = Compression::sniff(Box::new(Cursor::new(compressed.clone()))).unwrap();
+        assert_eq!(detected, compression);
+        assert_eq!(input.next_line().unwrap(), Some(Some("first".to_string())));
+        assert_eq!(input.next_line().unwrap(), Some(Some("second".to_string())));
+        assert_eq!(input.next_line().unwrap(), None);
+        assert_eq!(input.bytes_read(), compressed.len() as u64);
+        assert_eq!(input.total_bytes(), Some(compressed.len() as u64));
+    }
+
+    #[test]
+    fn test_input_decompresses_concatenated_gzip_members() {
+        let mut data = compress(Compression::Gzip, b"first\n");
+        data.extend(compress(Compression::Gzip, b"second\n"));
+        let mut input = LogSource::Reader(Box::new(Cursor::new(data)))
+            .open()
+            .unwrap();
+
+        assert_eq!(input.next_line().unwrap(), Some(Some("first".to_string())));
+        assert_eq!(input.next_line().unwrap(), Some(Some("second".to_string())));
+        assert_eq!(input.next_line().unwrap(), None);
+    }
+
+    #[test]
+    fn test_input_short_plain_input() {
+        let mut input = LogSource::Reader(Box::new(Cursor::new(b"x".to_vec())))
+            .open()
+            .unwrap();
+
+        assert_eq!(input.next_line().unwrap(), Some(Some("x".to_string())));
+        assert_eq!(input.next_line().unwrap(), None);
+    }
+
+    fn write_rotated_set(dir: &Path) {
+        fs::write(
+            dir.join("reth.log"),
+            "2024-06-07T09:06:20.873354Z  INFO newest\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("reth.log.1.gz"),
+            compress(
+                Compression::Gzip,
+                b"no timestamp yet\n2024-06-07T09:05:20.873354Z  INFO oldest\n",
+            ),
+        )
+        .unwrap();
+        fs::write(dir.join("reth.log.2"), "no timestamps at all\n").unwrap();
+        fs::write(dir.join("notes.txt"), "not a log\n").unwrap();
+        fs::write(dir.join("state.json"), "{\"version\":1}\n").unwrap();
+        fs::write(dir.join("reth-summary.json"), "{\"pipelines\":[]}\n").unwrap();
+        fs::write(
+            dir.join(".hidden"),
+            "2024-06-07T00:00:00.000000Z  INFO hidden\n",
+        )
+        .unwrap();
+    }
+
+    fn read_all(input: &mut Input) -> Vec<String> {
+        let mut lines = Vec::new();
+        while let Some(line) = input.next_line().unwrap() {
+            lines.push(line.unwrap());
+        }
+        lines
+    }
+
+    #[test]
+    fn test_input_directory_is_ordered_by_first_timestamp() {
+        let dir = tempfile::tempdir().unwrap();
+        write_rotated_set(dir.path());
+
+        let source = LogSource::from_arg(dir.path().to_str().unwrap());
+        assert!(matches!(source, LogSource::Directory(_)));
+        let mut input = source.open().unwrap();
+
+        assert_eq!(
+            read_all(&mut input),
+            vec![
+                "no timestamp yet",
+                "2024-06-07T09:05:20.873354Z  INFO oldest",
+                "2024-06-07T09:06:20.873354Z  INFO newest",
+                "no timestamps at all",
+            ]
+        );
+        assert_eq!(input.bytes_read(), input.total_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_input_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        write_rotated_set(dir.path());
+
+        let pattern = format!("{}/reth.log*", dir.path().display());
+        let source = LogSource::from_arg(&pattern);
+        assert!(matches!(source, LogSource::Glob(_)));
+        let mut input = source.open().unwrap();
+
+        assert_eq!(
+            read_all(&mut input),
+            vec![
+                "no timestamp yet",
+                "2024-06-07T09:05:20.873354Z  INFO oldest",
+                "2024-06-07T09:06:20.873354Z  INFO newest",
+                "no timestamps at all",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rotation_base() {
+        assert_eq!(rotation_base("reth.log"), Some("reth.log"));
+        assert_eq!(rotation_base("reth.log.1.gz"), Some("reth.log"));
+        assert_eq!(rotation_base("reth.log.2024-06-07"), Some("reth.log"));
+        assert_eq!(rotation_base("reth.log-20240607.zst"), Some("reth.log"));
+        assert_eq!(rotation_base("reth.log.json"), None);
+        assert_eq!(rotation_base("reth.logger"), None);
+        assert_eq!(rotation_base("state.json"), None);
+    }
+
+    #[test]
+    fn test_input_directory_with_several_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("geth.log"), "geth\n").unwrap();
+        fs::write(dir.path().join("lighthouse.log"), "lighthouse\n").unwrap();
+        assert!(LogSource::Directory(dir.path().to_path_buf())
+            .open()
+            .is_err());
+
+        fs::write(dir.path().join("reth.log"), "reth\n").unwrap();
+        let mut input = LogSource::Directory(dir.path().to_path_buf())
+            .open()
+            .unwrap();
+        assert_eq!(read_all(&mut input), vec!["reth"]);
+    }
+
+    #[test]
+    fn test_input_glob_without_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let pattern = format!("{}/reth.log*", dir.path().display());
+
+        assert!(LogSource::Glob(pattern).open().is_err());
+    }
+
+    #[test]
+    fn test_input_follow_appended_lines() {
+        let mut log_file = NamedTempFile::new().unwrap();
+        write!(log_file, "first\nsec").unwrap();
+
+        let mut input = LogSource::File(log_file.path().to_path_buf())
+            .open()
+            .unwrap();
+        input.follow().unwrap();
+
+        assert_eq!(input.next_line().unwrap(), Some(Some("first".to_string())));
+        assert_eq!(input.next_line().unwrap(), None);
+
+        write!(log_file, "ond\nthird\n").unwrap();
+        assert_eq!(input.next_line().unwrap(), Some(Some("second".to_string())));
+        assert_eq!(input.next_line().unwrap(), Some(Some("third".to_string())));
+        assert_eq!(input.next_line().unwrap(), None);
+    }
+
+    #[test]
+    fn test_input_follow_truncation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reth.log");
+        fs::write(&path, "first\nsecond\n").unwrap();
+
+        let mut input = LogSource::File(path.clone()).open().unwrap();
+        input.follow().unwrap();
+        assert_eq!(read_all(&mut input), vec!["first", "second"]);
+
+        fs::write(&path, "new\n").unwrap();
+        input.reopen_if_replaced().unwrap();
+        assert_eq!(read_all(&mut input), vec!["new"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_input_follow_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reth.log");
+        fs::write(&path, "first\n").unwrap();
+
+        let mut input = LogSource::File(path.clone()).open().unwrap();
+        input.follow().unwrap();
+        assert_eq!(read_all(&mut input), vec!["first"]);
+
+        input.reopen_if_replaced().unwrap();
+        assert_eq!(read_all(&mut input), Vec::<String>::new());
+
+        fs::rename(&path, dir.path().join("reth.log.1")).unwrap();
+        input.reopen_if_replaced().unwrap();
+        fs::write(&path, "rotated\nfile\n").unwrap();
+        input.reopen_if_replaced().unwrap();
+        assert_eq!(read_all(&mut input), vec!["rotated", "file"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_input_follow_rotation_reads_rotated_file_to_the_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reth.log");
+        fs::write(&path, "first\n").unwrap();
+
+        let mut input = LogSource::File(path.clone()).open().unwrap();
+        input.follow().unwrap();
+        assert_eq!(read_all(&mut input), vec!["first"]);
+
+        let mut log_file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        write!(log_file, "second\nthi").unwrap();
+        assert_eq!(read_all(&mut input), vec!["second"]);
+        write!(log_file, "rd\nfour").unwrap();
+        assert_eq!(read_all(&mut input), vec!["third"]);
+        write!(log_file, "th\nlast").unwrap();
+        assert_eq!(read_all(&mut input), vec!["fourth"]);
+        fs::rename(&path, dir.path().join("reth.log.1")).unwrap();
+        fs::write(&path, "rotated\n").unwrap();
+
+        input.reopen_if_replaced().unwrap();
+        assert_eq!(read_all(&mut input), vec!["last", "rotated"]);
+        assert_eq!(input.resume_offset(), Some(8));
+    }
+
+    #[test]
+    fn test_input_follow_requires_plain_file() {
+        let mut input = LogSource::Reader(Box::new(Cursor::new(b"x\n".to_vec())))
+            .open()
+            .unwrap();
+        assert!(input.follow().is_err());
+
+        let mut log_file = NamedTempFile::new().unwrap();
+        log_file
+            .write_all(&compress(Compression::Gzip, b"x\n"))
+            .unwrap();
+        let mut input = LogSource::File(log_file.path().to_path_buf())
+            .open()
+            .unwrap();
+        assert!(input.follow().is_err());
+    }
+
+    #[test]
+    fn test_input_resume_offset() {
+        let mut log_file = NamedTempFile::new().unwrap();
+        write!(log_file, "first\nsecond\nthird").unwrap();
+
+        let mut input = LogSource::File(log_file.path().to_path_buf())
+            .open()
+            .unwrap();
+        assert_eq!(input.next_line().unwrap(), Some(Some("first".to_string())));
+        assert_eq!(input.resume_offset(), Some(6));
+
+        let mut input = LogSource::File(log_file.path().to_path_buf())
+            .open_at(6)
+            .unwrap();
+        assert_eq!(read_all(&mut input), vec!["second", "third"]);
+        assert_eq!(input.resume_offset(), Some(18));
+
+        let mut input = LogSource::File(log_file.path().to_path_buf())
+            .open_at(6)
+            .unwrap();
+        input.follow().unwrap();
+        assert_eq!(read_all(&mut input), vec!["second"]);
+        assert_eq!(input.resume_offset(), Some(13));
+    }
+
+    #[test]
+    fn test_input_resume_offset_unavailable() {
+        let mut input = LogSource::Reader(Box::new(Cursor::new(b"x\n".to_vec())))
+            .open()
+            .unwrap();
+        read_all(&mut input);
+        assert_eq!(input.resume_offset(), None);
+        assert!(LogSource::Reader(Box::new(Cursor::new(b"x\n".to_vec())))
+            .open_at(1)
+            .is_err());
+
+        let mut log_file = NamedTempFile::new().unwrap();
+        log_file
+            .write_all(&compress(Compression::Gzip, b"x\n"))
+            .unwrap();
+        let mut input = LogSource::File(log_file.path().to_path_buf())
+            .open()
+            .unwrap();
+        read_all(&mut input);
+        assert_eq!(input.resume_offset(), None);
+    }
+}
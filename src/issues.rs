@@ -0,0 +1,267 @@
+use crate::time::format_time;
+use eyre::Result;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// Levels of the lines reported as issues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Level {
+    Error,
+    Warn,
+}
+
+impl Level {
+    pub(crate) const ALL: [Level; 2] = [Level::Error, Level::Warn];
+
+    /// The level as written by reth, in text and JSON logs alike.
+    pub(crate) fn literal(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+        }
+    }
+
+    pub(crate) fn parse(name: &str) -> Option<Self> {
+        Level::ALL
+            .into_iter()
+            .find(|level| level.literal().eq_ignore_ascii_case(name))
+    }
+}
+
+/// A line logged at a level reported as an issue.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LogIssue {
+    pub(crate) level: Level,
+    /// Tracing target of the line, when logged.
+    pub(crate) target: Option<String>,
+    /// Message of the line, with its fields.
+    pub(crate) message: String,
+    pub(crate) timestamp: Option<SystemTime>,
+}
+
+/// Turns messages into templates by masking what varies between lines of
+/// the same kind: peer IDs, hashes and numbers.
+#[derive(Clone)]
+pub(crate) struct MessageTemplates {
+    masks: Regex,
+}
+
+impl MessageTemplates {
+    pub(crate) fn new() -> Result<Self> {
+        Ok(MessageTemplates {
+            masks: Regex::new(
+                r"(?<peer>\b(?:peer_id|peer|remote_peer_id|node_id)=)\S+|(?<hash>\b0x[0-9a-fA-F]+\b|\b[0-9a-fA-F]{16,}\b)|(?<number>\b\d+(?:\.\d+)?)",
+            )?,
+        })
+    }
+
+    /// Template of `message`, e.g. `Failed to connect peer_id=<peer> attempt=<n>`.
+    pub(crate) fn of(&self, message: &str) -> String {
+        self.masks
+            .replace_all(message.trim(), |caps: &Captures| {
+                if let Some(key) = caps.name("peer") {
+                    format!("{}<peer>", key.as_str())
+                } else if caps.name("hash").is_some() {
+                    "<hash>".to_string()
+                } else {
+                    "<n>".to_string()
+                }
+            })
+            .into_owned()
+    }
+}
+
+/// Lines of the same level, target and message template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IssueGroup {
+    pub level: Level,
+    pub target: Option<String>,
+    pub template: String,
+    pub count: usize,
+    pub first_seen: Option<SystemTime>,
+    pub last_seen: Option<SystemTime>,
+    /// Stages that were running when the lines were logged, in the order
+    /// they were first seen.
+    pub stages: Vec<String>,
+    /// The first line of the group.
+    pub sample: String,
+}
+
+impl IssueGroup {
+    /// Adds a line to the group of its template, logged while `stage` was
+    /// running.
+    pub(crate) fn record(
+        groups: &mut Vec<IssueGroup>,
+        issue: LogIssue,
+        template: String,
+        stage: Option<&str>,
+        line: &str,
+    ) {
+        let index = match groups.iter().position(|group| {
+            group.level == issue.level && group.target == issue.target && group.template == template
+        }) {
+            Some(index) => index,
+            None => {
+                groups.push(IssueGroup {
+                    level: issue.level,
+                    target: issue.target,
+                    template,
+                    count: 0,
+                    first_seen: issue.timestamp,
+                    last_seen: issue.timestamp,
+                    stages: Vec::new(),
+                    sample: line.trim().to_string(),
+                });
+                groups.len() - 1
+            }
+        };
+
+        let group = &mut groups[index];
+        group.count += 1;
+        if let Some(timestamp) = issue.timestamp {
+            group.first_seen = Some(
+                group
+                    .first_seen
+                    .map_or(timestamp, |first| first.min(timestamp)),
+            );
+            group.last_seen = Some(
+                group
+                    .last_seen
+                    .map_or(timestamp, |last| last.max(timestamp)),
+            );
+        }
+        if let Some(stage) = stage {
+            if !group.stages.iter().any(|seen| seen == stage) {
+                group.stages.push(stage.to_string());
+            }
+        }
+    }
+
+    /// Prints the groups, errors first and the most frequent first, if any.
+    pub(crate) fn print_summary<W: std::io::Write>(groups: &[IssueGroup], writer: &mut W) {
+        if groups.is_empty() {
+            return;
+        }
+        let count = |level: Level| -> usize {
+            groups
+                .iter()
+                .filter(|group| group.level == level)
+                .map(|group| group.count)
+                .sum()
+        };
+        let (errors, warnings) = (count(Level::Error), count(Level::Warn));
+        writeln!(
+            writer,
+            "Warnings and Errors: {errors} error{}, {warnings} warning{}",
+            if errors == 1 { "" } else { "s" },
+            if warnings == 1 { "" } else { "s" }
+        )
+        .unwrap();
+
+        let mut sorted: Vec<&IssueGroup> = groups.iter().collect();
+        sorted.sort_by(|a, b| {
+            a.level
+                .cmp(&b.level)
+                .then(b.count.cmp(&a.count))
+                .then(a.first_seen.cmp(&b.first_seen))
+        });
+        for group in sorted {
+            let mut description = format!("  {}x {}", group.count, group.level.literal());
+            if let Some(target) = &group.target {
+                description.push_str(&format!(" {target}:"));
+            }
+            writeln!(writer, "{description} {}", group.template).unwrap();
+
+            let stages = if group.stages.is_empty() {
+                "outside of stages".to_string()
+            } else {
+                format!("during {}", group.stages.join(", "))
+            };
+            writeln!(
+                writer,
+                "    first {}, last {}, {stages}",
+                format_time(group.first_seen),
+                format_time(group.last_seen)
+            )
+            .unwrap();
+            writeln!(writer, "    sample: {}", group.sample).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_message_templates() {
+        let templates = MessageTemplates::new().unwrap();
+        assert_eq!(
+            templates.of("Failed to connect peer_id=0x9f3a1c remote_addr=10.0.0.1:30303 attempt=3"),
+            "Failed to connect peer_id=<peer> remote_addr=<n>.<n>:<n> attempt=<n>"
+        );
+        assert_eq!(
+            templates.of("Invalid block error on new payload invalid_hash=0xabcdef invalid_number=Some(20124790)"),
+            "Invalid block error on new payload invalid_hash=<hash> invalid_number=Some(<n>)"
+        );
+        assert_eq!(
+            templates.of("Beacon client online, but no consensus updates received for a while. period=300.5s"),
+            "Beacon client online, but no consensus updates received for a while. period=<n>s"
+        );
+    }
+
+    #[test]
+    fn test_issue_groups() {
+        let templates = MessageTemplates::new().unwrap();
+        let timestamp = |seconds| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds));
+        let mut groups = Vec::new();
+        for (level, message, seconds, stage) in [
+            (
+                Level::Warn,
+                "Session error peer_id=0x01 err=timeout",
+                1_718_000_000,
+                Some("Headers"),
+            ),
+            (Level::Error, "Database error code=12", 1_718_000_100, None),
+            (
+                Level::Warn,
+                "Session error peer_id=0x02 err=timeout",
+                1_718_000_050,
+                Some("Bodies"),
+            ),
+            (
+                Level::Warn,
+                "Session error peer_id=0x03 err=timeout",
+                1_718_000_060,
+                Some("Bodies"),
+            ),
+        ] {
+            let issue = LogIssue {
+                level,
+                target: Some("net::session".to_string()),
+                message: message.to_string(),
+                timestamp: timestamp(seconds),
+            };
+            let template = templates.of(&issue.message);
+            IssueGroup::record(&mut groups, issue, template, stage, message);
+        }
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].count, 3);
+        assert_eq!(groups[0].stages, ["Headers", "Bodies"]);
+
+        let mut output = Vec::new();
+        IssueGroup::print_summary(&groups, &mut output);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Warnings and Errors: 1 error, 3 warnings\n  \
+             1x ERROR net::session: Database error code=<n>\n    \
+             first 2024-06-10 06:15:00, last 2024-06-10 06:15:00, outside of stages\n    \
+             sample: Database error code=12\n  \
+             3x WARN net::session: Session error peer_id=<peer> err=timeout\n    \
+             first 2024-06-10 06:13:20, last 2024-06-10 06:14:20, during Headers, Bodies\n    \
+             sample: Session error peer_id=0x01 err=timeout\n"
+        );
+    }
+}
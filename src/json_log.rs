@@ -0,0 +1,523 @@
+use crate::{
+    engine::EngineCall,
+    event::{Event, Progress, StageStatus},
+    format::EntryMetadata,
+    health::StatusSample,
+    issues::{Level, LogIssue},
+    live_sync::{parse_gas, CanonicalBlock},
+    pipeline::{PipelineStages, StageCheckpoint},
+    time::{parse_elapsed, parse_eta, parse_rfc3339, TimestampExtractor},
+};
+use eyre::Result;
+use serde_json::{Map, Value};
+use std::time::SystemTime;
+
+/// Parses a line written by reth with `--log.stdout.format json` or
+/// `--log.file.format json`, e.g.
+///
+/// `{"timestamp":"2024-06-07T09:05:20.873354Z","level":"INFO","fields":{"message":"Preparing stage","pipeline_stages":"1/12","stage":"Headers","checkpoint":20037711,"target":"None"},"target":"reth_node_events::node"}`
+///
+/// Returns `None` for lines which aren't JSON objects, so they can be parsed
+/// as text instead.
+pub(crate) fn parse_json_line(
+    line: &str,
+    metadata: &EntryMetadata,
+    timestamps: &mut TimestampExtractor,
+) -> Option<Result<Option<Event>>> {
+    if !line.starts_with('{') {
+        return None;
+    }
+    let Ok(Value::Object(object)) = serde_json::from_str::<Value>(line) else {
+        return None;
+    };
+
+    Some(parse_json_object(line, &object, metadata, timestamps))
+}
+
+fn parse_json_object(
+    line: &str,
+    object: &Map<String, Value>,
+    metadata: &EntryMetadata,
+    timestamps: &mut TimestampExtractor,
+) -> Result<Option<Event>> {
+    // Events are nested in `fields` unless the subscriber flattens them.
+    let fields = match object.get("fields") {
+        Some(Value::Object(fields)) => fields,
+        _ => object,
+    };
+    let Some(message) = fields.get("message").and_then(Value::as_str) else {
+        return Ok(None);
+    };
+
+    if message == "Validated state root" {
+        let block = ["number", "block_number", "block"]
+            .iter()
+            .find_map(|name| field_str(fields, name)?.parse().ok());
+        let Some(elapsed) =
+            field_str(fields, "elapsed").and_then(|elapsed| parse_elapsed(&elapsed))
+        else {
+            return Ok(None);
+        };
+        let timestamp = entry_timestamp(line, object, metadata, timestamps)?;
+        return Ok(Some(Event::StateRoot {
+            block,
+            timestamp,
+            elapsed,
+        }));
+    }
+
+    if message == "Block added to canonical chain" {
+        let (Some(number), Some(elapsed)) = (
+            field_str(fields, "number").and_then(|number| number.parse().ok()),
+            field_str(fields, "elapsed").and_then(|elapsed| parse_elapsed(&elapsed)),
+        ) else {
+            return Ok(None);
+        };
+        let mgas = field_str(fields, "mgas")
+            .and_then(|mgas| mgas.parse::<f64>().ok())
+            .map(|mgas| (mgas * 1e6).round() as u64);
+        let block = CanonicalBlock {
+            number,
+            hash: field_str(fields, "hash"),
+            timestamp: entry_timestamp(line, object, metadata, timestamps)?,
+            txs: field_str(fields, "txs").and_then(|txs| txs.parse().ok()),
+            gas_used: field_str(fields, "gas")
+                .or_else(|| field_str(fields, "gas_used"))
+                .and_then(|gas| parse_gas(&gas))
+                .or(mgas),
+            elapsed,
+        };
+        return Ok(Some(Event::BlockAdded { block }));
+    }
+
+    if let Some((method, status)) = EngineCall::classify(message) {
+        let call = EngineCall {
+            method,
+            timestamp: entry_timestamp(line, object, metadata, timestamps)?,
+            status,
+            block: ["number", "invalid_number"]
+                .iter()
+                .find_map(|name| field_str(fields, name)?.parse().ok()),
+            hash: ["hash", "head_block_hash", "invalid_hash"]
+                .iter()
+                .find_map(|name| field_str(fields, name)),
+            elapsed: field_str(fields, "elapsed").and_then(|elapsed| parse_elapsed(&elapsed)),
+        };
+        return Ok(Some(Event::EngineCall { call }));
+    }
+
+    if message.starts_with("Starting reth") {
+        let timestamp = entry_timestamp(line, object, metadata, timestamps)?;
+        return Ok(Some(Event::Startup { timestamp }));
+    }
+
+    let stage = field_str(fields, "stage");
+    let progress = field_str(fields, "stage_progress")
+        .and_then(|progress| progress.trim_end_matches('%').parse().ok())
+        .map(|percent| Progress {
+            percent,
+            eta: field_str(fields, "stage_eta").and_then(|eta| parse_eta(&eta)),
+        });
+    let checkpoint = StageCheckpoint {
+        checkpoint: field_str(fields, "checkpoint").and_then(|value| value.parse().ok()),
+        target: field_str(fields, "target").and_then(|value| value.parse().ok()),
+    };
+
+    if message == "Status" {
+        let number = |name: &str| field_str(fields, name).and_then(|value| value.parse().ok());
+        let sample = StatusSample {
+            timestamp: entry_timestamp(line, object, metadata, timestamps)?,
+            connected_peers: number("connected_peers"),
+            freelist: number("freelist"),
+            latest_block: number("latest_block"),
+        };
+        let stage = match (stage, progress) {
+            (Some(stage), Some(progress)) => Some(StageStatus {
+                stage,
+                checkpoint,
+                progress,
+            }),
+            _ => None,
+        };
+        return Ok(Some(Event::Status { sample, stage }));
+    }
+
+    // Other events are about stages: their start, end or unwind.
+    let Some(stage) = stage else {
+        return Ok(None);
+    };
+    let is_stage_line = matches!(
+        message,
+        "Preparing stage"
+            | "Finished stage"
+            | "Committed stage progress"
+            | "Unwinding"
+            | "Unwound stage"
+    );
+    if !is_stage_line {
+        return Ok(None);
+    }
+
+    let timestamp = entry_timestamp(line, object, metadata, timestamps)?;
+
+    Ok(Some(match message {
+        "Preparing stage" => Event::StageStart {
+            stage,
+            timestamp,
+            checkpoint,
+            pipeline_stages: field_str(fields, "pipeline_stages")
+                .and_then(|value| PipelineStages::parse(&value)),
+        },
+        "Finished stage" | "Committed stage progress" => Event::StageEnd {
+            stage,
+            timestamp,
+            checkpoint,
+            done: message == "Finished stage",
+            progress,
+        },
+        "Unwinding" => Event::UnwindStart {
+            stage,
+            timestamp,
+            checkpoint,
+        },
+        "Unwound stage" => Event::UnwindEnd {
+            stage,
+            timestamp,
+            checkpoint,
+        },
+        _ => return Ok(None),
+    }))
+}
+
+/// Parses a JSON line logged at a level reported as an issue, with the fields
+/// of the event written after its message in the order they were logged,
+/// like in text logs. Returns `None` for lines which aren't JSON objects, like
+/// [`parse_json_line`].
+pub(crate) fn parse_json_issue(
+    line: &str,
+    metadata: &EntryMetadata,
+    timestamps: &mut TimestampExtractor,
+) -> Option<Option<LogIssue>> {
+    if !line.starts_with('{') {
+        return None;
+    }
+    let Ok(Value::Object(object)) = serde_json::from_str::<Value>(line) else {
+        return None;
+    };
+    let Some(level) = object
+        .get("level")
+        .and_then(Value::as_str)
+        .and_then(Level::parse)
+    else {
+        return Some(None);
+    };
+
+    let (fields, flattened) = match object.get("fields") {
+        Some(Value::Object(fields)) => (fields, false),
+        _ => (&object, true),
+    };
+    let mut message = field_str(fields, "message").unwrap_or_default();
+    for name in fields.keys() {
+        let is_metadata = name == "message"
+            || (flattened && matches!(name.as_str(), "timestamp" | "level" | "target"));
+        if is_metadata {
+            continue;
+        }
+        if let Some(value) = field_str(fields, name) {
+            message.push_str(&format!(" {name}={value}"));
+        }
+    }
+
+    Some(Some(LogIssue {
+        level,
+        target: object
+            .get("target")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        message,
+        timestamp: entry_timestamp(line, &object, metadata, timestamps).ok(),
+    }))
+}
+
+/// Timestamp of the entry, picked between the one written by reth and the
+/// other sources.
+fn entry_timestamp(
+    line: &str,
+    object: &Map<String, Value>,
+    metadata: &EntryMetadata,
+    timestamps: &mut TimestampExtractor,
+) -> Result<SystemTime> {
+    let reth = object
+        .get("timestamp")
+        .and_then(Value::as_str)
+        .and_then(parse_rfc3339);
+    timestamps.pick(reth, line, metadata)
+}
+
+/// Value of a field as text, whether it was recorded as a string, a number or
+/// with its `Debug` representation.
+fn field_str(fields: &Map<String, Value>, name: &str) -> Option<String> {
+    match fields.get(name)? {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{EngineMethod, PayloadStatus};
+
+    fn parse(line: &str) -> Option<Event> {
+        let mut timestamps = TimestampExtractor::new().unwrap();
+        parse_json_line(line, &EntryMetadata::default(), &mut timestamps)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_stage_start() {
+        let line = r#"{"timestamp":"2024-06-07T09:05:20.873354Z","level":"INFO","fields":{"message":"Preparing stage","pipeline_stages":"1/12","stage":"Headers","checkpoint":20037711,"target":"None"},"target":"reth_node_events::node"}"#;
+
+        assert_eq!(
+            parse(line),
+            Some(Event::StageStart {
+                stage: "Headers".to_string(),
+                timestamp: parse_rfc3339("2024-06-07T09:05:20.873354Z").unwrap(),
+                checkpoint: StageCheckpoint {
+                    checkpoint: Some(20037711),
+                    target: None,
+                },
+                pipeline_stages: Some(PipelineStages {
+                    index: 1,
+                    count: 12
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_flattened_stage_end() {
+        let line = r#"{"timestamp":"2024-06-07T09:06:20.873354Z","level":"INFO","message":"Finished stage","pipeline_stages":"1/12","stage":"Headers","checkpoint":20038569,"target":"None","stage_progress":"100.00%"}"#;
+
+        assert_eq!(
+            parse(line),
+            Some(Event::StageEnd {
+                stage: "Headers".to_string(),
+                timestamp: parse_rfc3339("2024-06-07T09:06:20.873354Z").unwrap(),
+                checkpoint: StageCheckpoint {
+                    checkpoint: Some(20038569),
+                    target: None,
+                },
+                done: true,
+                progress: Some(Progress {
+                    percent: 100.0,
+                    eta: None,
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_stage_commit() {
+        let line = r#"{"timestamp":"2024-06-07T09:06:20.873354Z","level":"INFO","fields":{"message":"Committed stage progress","pipeline_stages":"2/12","stage":"Bodies","checkpoint":3000,"target":20110161,"stage_progress":"0.01%","stage_eta":"1h 2m 3s"},"target":"reth_node_events::node"}"#;
+
+        assert_eq!(
+            parse(line),
+            Some(Event::StageEnd {
+                stage: "Bodies".to_string(),
+                timestamp: parse_rfc3339("2024-06-07T09:06:20.873354Z").unwrap(),
+                checkpoint: StageCheckpoint {
+                    checkpoint: Some(3000),
+                    target: Some(20110161),
+                },
+                done: false,
+                progress: Some(Progress {
+                    percent: 0.01,
+                    eta: Some(std::time::Duration::from_secs(3723)),
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_stage_progress() {
+        let line = r#"{"timestamp":"2024-06-07T09:06:20.873354Z","level":"INFO","fields":{"message":"Status","connected_peers":10,"stage":"Bodies","checkpoint":1000,"target":20110161,"stage_progress":"0.00%","stage_eta":"unknown"},"target":"reth::cli"}"#;
+
+        assert_eq!(
+            parse(line),
+            Some(Event::Status {
+                sample: StatusSample {
+                    timestamp: parse_rfc3339("2024-06-07T09:06:20.873354Z").unwrap(),
+                    connected_peers: Some(10),
+                    freelist: None,
+                    latest_block: None,
+                },
+                stage: Some(StageStatus {
+                    stage: "Bodies".to_string(),
+                    checkpoint: StageCheckpoint {
+                        checkpoint: Some(1000),
+                        target: Some(20110161),
+                    },
+                    progress: Progress {
+                        percent: 0.0,
+                        eta: None,
+                    },
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_block_added() {
+        let line = r#"{"timestamp":"2024-06-19T09:21:22.123456Z","level":"INFO","fields":{"message":"Block added to canonical chain","number":20124789,"hash":"0xf0a3","peers":88,"txs":150,"gas":"12.34 Mgas","gas_throughput":"401.12 Mgas/second","full":"41.1%","elapsed":"30.712ms"},"target":"reth::cli"}"#;
+
+        assert_eq!(
+            parse(line),
+            Some(Event::BlockAdded {
+                block: CanonicalBlock {
+                    number: 20124789,
+                    hash: Some("0xf0a3".to_string()),
+                    timestamp: parse_rfc3339("2024-06-19T09:21:22.123456Z").unwrap(),
+                    txs: Some(150),
+                    gas_used: Some(12_340_000),
+                    elapsed: 0.030712,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_engine_call() {
+        let line = r#"{"timestamp":"2024-06-19T09:21:22.523456Z","level":"INFO","fields":{"message":"Received forkchoice updated message when syncing","head_block_hash":"0xf0a3","safe_block_hash":"0x11aa","finalized_block_hash":"0x22bb"},"target":"reth::cli"}"#;
+
+        assert_eq!(
+            parse(line),
+            Some(Event::EngineCall {
+                call: EngineCall {
+                    method: EngineMethod::ForkchoiceUpdated,
+                    timestamp: parse_rfc3339("2024-06-19T09:21:22.523456Z").unwrap(),
+                    status: Some(PayloadStatus::Syncing),
+                    block: None,
+                    hash: Some("0xf0a3".to_string()),
+                    elapsed: None,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_startup() {
+        let line = r#"{"timestamp":"2024-06-07T09:00:00.000000Z","level":"INFO","fields":{"message":"Starting reth","version":"1.0.0"},"target":"reth::cli"}"#;
+
+        assert_eq!(
+            parse(line),
+            Some(Event::Startup {
+                timestamp: parse_rfc3339("2024-06-07T09:00:00.000000Z").unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_unwind() {
+        let line = r#"{"timestamp":"2024-06-07T09:06:20.873354Z","level":"INFO","fields":{"message":"Unwinding","pipeline_stages":"12/12","stage":"Finish","checkpoint":20038569,"target":20038500},"target":"reth_node_events::node"}"#;
+
+        assert_eq!(
+            parse(line),
+            Some(Event::UnwindStart {
+                stage: "Finish".to_string(),
+                timestamp: parse_rfc3339("2024-06-07T09:06:20.873354Z").unwrap(),
+                checkpoint: StageCheckpoint {
+                    checkpoint: Some(20038569),
+                    target: Some(20038500),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_state_root() {
+        let line = r#"{"timestamp":"2024-06-07T09:06:20.873354Z","level":"DEBUG","fields":{"message":"Validated state root","number":20038569,"elapsed":"12.5ms"},"target":"engine::tree"}"#;
+
+        assert_eq!(
+            parse(line),
+            Some(Event::StateRoot {
+                block: Some(20038569),
+                timestamp: parse_rfc3339("2024-06-07T09:06:20.873354Z").unwrap(),
+                elapsed: 0.0125
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let line = r#"{"timestamp":"2024-06-07T09:06:20.873354Z","level":"INFO","fields":{"message":"Status","connected_peers":132,"freelist":748277,"latest_block":20110636},"target":"reth::cli"}"#;
+
+        assert_eq!(
+            parse(line),
+            Some(Event::Status {
+                sample: StatusSample {
+                    timestamp: parse_rfc3339("2024-06-07T09:06:20.873354Z").unwrap(),
+                    connected_peers: Some(132),
+                    freelist: Some(748277),
+                    latest_block: Some(20110636),
+                },
+                stage: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_issue() {
+        let mut timestamps = TimestampExtractor::new().unwrap();
+        let line = r#"{"timestamp":"2024-06-19T09:21:22.023456Z","level":"WARN","fields":{"message":"Invalid block error on new payload","invalid_hash":"0xf0a3","invalid_number":"Some(20124789)"},"target":"consensus::engine"}"#;
+        assert_eq!(
+            parse_json_issue(line, &EntryMetadata::default(), &mut timestamps),
+            Some(Some(LogIssue {
+                level: Level::Warn,
+                target: Some("consensus::engine".to_string()),
+                message: "Invalid block error on new payload invalid_hash=0xf0a3 invalid_number=Some(20124789)".to_string(),
+                timestamp: parse_rfc3339("2024-06-19T09:21:22.023456Z"),
+            }))
+        );
+
+        // Fields keep the order they were logged in, like in text logs.
+        let line = r#"{"timestamp":"2024-06-19T09:21:22.023456Z","level":"WARN","fields":{"message":"Session error","peer_id":"0x02","err":"timeout"},"target":"net::session"}"#;
+        assert_eq!(
+            parse_json_issue(line, &EntryMetadata::default(), &mut timestamps)
+                .unwrap()
+                .unwrap()
+                .message,
+            "Session error peer_id=0x02 err=timeout"
+        );
+
+        let line = r#"{"timestamp":"2024-06-19T09:21:22.023456Z","level":"INFO","fields":{"message":"Status","connected_peers":10}}"#;
+        assert_eq!(
+            parse_json_issue(line, &EntryMetadata::default(), &mut timestamps),
+            Some(None)
+        );
+        assert_eq!(
+            parse_json_issue(
+                "2024-06-19T09:21:22.023456Z  WARN text line",
+                &EntryMetadata::default(),
+                &mut timestamps
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_other_lines() {
+        let line = r#"{"timestamp":"2024-06-07T09:06:20.873354Z","level":"INFO","fields":{"message":"Transaction pool maintained","connected_peers":10},"target":"reth::cli"}"#;
+
+        assert_eq!(parse(line), None);
+        assert!(parse_json_line(
+            "2024-06-07T09:06:20.873354Z  INFO Status",
+            &EntryMetadata::default(),
+            &mut TimestampExtractor::new().unwrap()
+        )
+        .is_none());
+    }
+}
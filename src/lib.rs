@@ -1,5 +1,22 @@
+mod abort;
+mod ansi;
+mod engine;
+mod event;
+mod export;
+pub mod format;
+mod health;
+pub mod input;
+mod issues;
+mod json_log;
+mod line_parser;
+mod live_sync;
 mod log_processor;
+mod matcher;
+mod parallel;
 mod pipeline;
 pub mod runner;
+mod session;
+mod state;
+mod state_root;
 mod stats;
-mod time;
+pub mod time;
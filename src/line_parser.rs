@@ -0,0 +1,316 @@
+use crate::{
+    engine::EngineCall,
+    event::{Event, Progress, StageStatus},
+    format::EntryMetadata,
+    health::StatusSample,
+    issues::{LogIssue, MessageTemplates},
+    json_log::{parse_json_issue, parse_json_line},
+    live_sync::{parse_gas, CanonicalBlock},
+    matcher::{LineKind, LineMatcher, SYSLOG_PREFIX},
+    pipeline::{Interruption, PipelineStages, StageCheckpoint},
+    time::{parse_elapsed, parse_eta, TimestampExtractor, TimestampSource},
+};
+use eyre::Result;
+use regex::{Captures, Regex};
+use std::time::SystemTime;
+
+/// What a line tells on its own, before it is applied to the state of the
+/// processor in the order of the log.
+pub(crate) struct ParsedLine {
+    /// The event of the line, if it has one.
+    pub(crate) event: Result<Option<Event>>,
+    /// The line as an issue, with its message template, when it was logged
+    /// at a level reported as an issue.
+    pub(crate) issue: Option<(LogIssue, String)>,
+    /// PID of the process which wrote the line, when the log collector or
+    /// the syslog prefix tells.
+    pub(crate) pid: Option<u32>,
+    /// The line without its syslog prefix, when it may continue the report
+    /// of an abnormal termination.
+    pub(crate) continuation: Option<String>,
+}
+
+/// Extracts events and issues from lines independently of each other.
+#[derive(Clone)]
+pub(crate) struct LineParser {
+    matcher: LineMatcher,
+    timestamps: TimestampExtractor,
+    templates: MessageTemplates,
+    /// Syslog prefix of text lines, see [`SYSLOG_PREFIX`].
+    syslog_prefix: Regex,
+}
+
+impl LineParser {
+    pub(crate) fn new() -> Result<Self> {
+        Ok(LineParser {
+            matcher: LineMatcher::new()?,
+            timestamps: TimestampExtractor::new()?,
+            templates: MessageTemplates::new()?,
+            syslog_prefix: Regex::new(SYSLOG_PREFIX)?,
+        })
+    }
+
+    pub(crate) fn set_timestamp_priority(&mut self, priority: Vec<TimestampSource>) {
+        self.timestamps.set_priority(priority);
+    }
+
+    pub(crate) fn timestamp_reference(&self) -> Option<SystemTime> {
+        self.timestamps.reference()
+    }
+
+    pub(crate) fn set_timestamp_reference(&mut self, reference: Option<SystemTime>) {
+        self.timestamps.set_reference(reference);
+    }
+
+    /// Timestamp written by reth in a line, if any.
+    pub(crate) fn reth_timestamp(&self, line: &str) -> Option<SystemTime> {
+        self.timestamps.reth_timestamp(line)
+    }
+
+    /// Whether parsing `line` may produce an event or issue.
+    pub(crate) fn is_relevant(&self, line: &str) -> bool {
+        self.matcher.is_candidate(line)
+    }
+
+    /// Whether a line may continue the report of an abnormal termination.
+    pub(crate) fn continues_report(&self, line: &str) -> bool {
+        !line.starts_with('{') && !self.timestamps.is_timestamped(line)
+    }
+
+    /// Parses a text or JSON line read from a log collector.
+    pub(crate) fn parse(
+        &mut self,
+        line: &str,
+        metadata: &EntryMetadata,
+        in_report: bool,
+    ) -> ParsedLine {
+        let continuation = (in_report && self.continues_report(line))
+            .then(|| self.strip_syslog_prefix(line).to_string());
+        if !self.matcher.is_candidate(line) {
+            return ParsedLine {
+                event: Ok(None),
+                issue: None,
+                pid: continuation.as_ref().and_then(|_| self.pid(line, metadata)),
+                continuation,
+            };
+        }
+
+        let issue = self.parse_issue(line, metadata).map(|issue| {
+            let template = self.templates.of(&issue.message);
+            (issue, template)
+        });
+        let event = match parse_json_line(line, metadata, &mut self.timestamps) {
+            Some(event) => event,
+            None => self.parse_text_line(line, metadata),
+        };
+        let pid = match (&event, &continuation) {
+            (Ok(Some(_)), _) | (_, Some(_)) => self.pid(line, metadata),
+            _ => None,
+        };
+        ParsedLine {
+            event,
+            issue,
+            pid,
+            continuation,
+        }
+    }
+
+    /// PID of the process which wrote a line, when the log collector or the
+    /// syslog prefix tells.
+    fn pid(&self, line: &str, metadata: &EntryMetadata) -> Option<u32> {
+        metadata.pid.or_else(|| {
+            self.syslog_prefix
+                .captures(line)
+                .and_then(|caps| caps.get(1)?.as_str().parse().ok())
+        })
+    }
+
+    /// Message of a text line, without its syslog prefix if it has one.
+    fn strip_syslog_prefix<'l>(&self, line: &'l str) -> &'l str {
+        self.syslog_prefix
+            .find(line)
+            .map_or(line, |prefix| &line[prefix.end()..])
+    }
+
+    fn parse_text_line(&mut self, line: &str, metadata: &EntryMetadata) -> Result<Option<Event>> {
+        let Some((kind, caps)) = self.matcher.captures(line) else {
+            return Ok(None);
+        };
+
+        Ok(Some(match kind {
+            LineKind::StageStart => Event::StageStart {
+                stage: caps["stage"].to_string(),
+                timestamp: self.timestamps.extract(line, metadata)?,
+                checkpoint: StageCheckpoint::parse(&caps["checkpoint"], &caps["target"]),
+                pipeline_stages: Some(PipelineStages {
+                    index: caps["index"].parse()?,
+                    count: caps["count"].parse()?,
+                }),
+            },
+            LineKind::StageEnd | LineKind::StageCommit => Event::StageEnd {
+                stage: caps["stage"].to_string(),
+                timestamp: self.timestamps.extract(line, metadata)?,
+                checkpoint: StageCheckpoint::parse(&caps["checkpoint"], &caps["target"]),
+                done: kind == LineKind::StageEnd,
+                progress: parse_progress(&caps)?,
+            },
+            LineKind::Status => {
+                let number = |name: &str| {
+                    caps.name(name)
+                        .and_then(|value| value.as_str().parse().ok())
+                };
+                let stage = match (caps.name("stage"), parse_progress(&caps)?) {
+                    (Some(stage), Some(progress)) => Some(StageStatus {
+                        stage: stage.as_str().to_string(),
+                        checkpoint: StageCheckpoint::parse(&caps["checkpoint"], &caps["target"]),
+                        progress,
+                    }),
+                    _ => None,
+                };
+                Event::Status {
+                    sample: StatusSample {
+                        timestamp: self.timestamps.extract(line, metadata)?,
+                        connected_peers: number("peers"),
+                        freelist: number("freelist"),
+                        latest_block: number("latest_block"),
+                    },
+                    stage,
+                }
+            }
+            LineKind::UnwindStart => Event::UnwindStart {
+                stage: caps["stage"].to_string(),
+                timestamp: self.timestamps.extract(line, metadata)?,
+                checkpoint: StageCheckpoint::parse(&caps["checkpoint"], &caps["target"]),
+            },
+            LineKind::UnwindEnd => Event::UnwindEnd {
+                stage: caps["stage"].to_string(),
+                timestamp: self.timestamps.extract(line, metadata)?,
+                checkpoint: StageCheckpoint::parse(&caps["checkpoint"], ""),
+            },
+            LineKind::BlockAdded => {
+                let number = |name: &str| {
+                    caps.name(name)
+                        .and_then(|value| value.as_str().parse().ok())
+                };
+                Event::BlockAdded {
+                    block: CanonicalBlock {
+                        number: caps["number"].parse()?,
+                        hash: Some(caps["hash"].to_string()),
+                        timestamp: self.timestamps.extract(line, metadata)?,
+                        txs: number("txs"),
+                        gas_used: caps
+                            .name("gas")
+                            .and_then(|gas| parse_gas(gas.as_str()))
+                            .or_else(|| {
+                                let mgas: f64 = caps.name("mgas")?.as_str().parse().ok()?;
+                                Some((mgas * 1e6).round() as u64)
+                            }),
+                        elapsed: parse_elapsed(&caps["elapsed"])
+                            .ok_or_else(|| eyre::eyre!("invalid block elapsed time"))?,
+                    },
+                }
+            }
+            LineKind::ForkchoiceUpdated
+            | LineKind::ForkchoiceSyncing
+            | LineKind::ForkchoiceInvalid
+            | LineKind::NewPayload
+            | LineKind::NewPayloadInvalid => {
+                let (method, status) = EngineCall::classify(kind.literal())
+                    .ok_or_else(|| eyre::eyre!("{kind:?} lines aren't Engine API calls"))?;
+                Event::EngineCall {
+                    call: EngineCall {
+                        method,
+                        timestamp: self.timestamps.extract(line, metadata)?,
+                        status,
+                        block: caps
+                            .name("number")
+                            .and_then(|number| number.as_str().parse().ok()),
+                        hash: caps.name("hash").map(|hash| hash.as_str().to_string()),
+                        elapsed: caps
+                            .name("elapsed")
+                            .and_then(|elapsed| parse_elapsed(elapsed.as_str())),
+                    },
+                }
+            }
+            LineKind::Startup => Event::Startup {
+                timestamp: self.timestamps.extract(line, metadata)?,
+            },
+            LineKind::Panic
+            | LineKind::OutOfMemory
+            | LineKind::OomKiller
+            | LineKind::AllocationFailed
+            | LineKind::ProcessExited
+            | LineKind::ErrorExit => {
+                let Some(reason) = abort_reason(kind, &caps) else {
+                    return Ok(None);
+                };
+                Event::Abort {
+                    timestamp: self.timestamps.extract(line, metadata).ok(),
+                    reason,
+                    report: self.strip_syslog_prefix(line).to_string(),
+                    supervisor: matches!(
+                        kind,
+                        LineKind::OutOfMemory | LineKind::OomKiller | LineKind::ProcessExited
+                    ),
+                }
+            }
+            LineKind::StateRoot => Event::StateRoot {
+                block: caps
+                    .name("block")
+                    .and_then(|block| block.as_str().parse().ok()),
+                timestamp: self.timestamps.extract(line, metadata)?,
+                elapsed: parse_elapsed(&caps["elapsed"])
+                    .ok_or_else(|| eyre::eyre!("invalid state root elapsed time"))?,
+            },
+        }))
+    }
+
+    /// The line as an issue, when it was logged at a level reported as one.
+    fn parse_issue(&mut self, line: &str, metadata: &EntryMetadata) -> Option<LogIssue> {
+        self.matcher.issue_level(line)?;
+        match parse_json_issue(line, metadata, &mut self.timestamps) {
+            Some(issue) => issue,
+            None => self.matcher.issue(line).map(|(level, caps)| LogIssue {
+                level,
+                target: caps
+                    .name("target")
+                    .map(|target| target.as_str().to_string()),
+                message: caps["message"].to_string(),
+                timestamp: self.timestamps.extract(line, metadata).ok(),
+            }),
+        }
+    }
+}
+
+/// Why reth terminated according to a line of an abnormal termination kind,
+/// `None` when it exited normally or another process ran out of memory.
+fn abort_reason(kind: LineKind, caps: &Captures) -> Option<Interruption> {
+    match kind {
+        LineKind::Panic => Some(Interruption::Panic),
+        LineKind::OutOfMemory if !caps["process"].contains("reth") => None,
+        LineKind::OutOfMemory | LineKind::OomKiller | LineKind::AllocationFailed => {
+            Some(Interruption::OutOfMemory)
+        }
+        LineKind::ProcessExited => {
+            let signal = caps.name("signal").map(|signal| signal.as_str());
+            match (&caps["code"], &caps["status"], signal) {
+                ("exited", "0", _) | (_, _, Some("TERM" | "INT")) => None,
+                ("exited", _, _) => Some(Interruption::ErrorExit),
+                _ => Some(Interruption::Crash),
+            }
+        }
+        LineKind::ErrorExit => Some(Interruption::ErrorExit),
+        _ => None,
+    }
+}
+
+/// Progress and ETA captured by the pattern of a stage line.
+fn parse_progress(caps: &Captures) -> Result<Option<Progress>> {
+    let Some(percent) = caps.name("progress") else {
+        return Ok(None);
+    };
+    Ok(Some(Progress {
+        percent: percent.as_str().parse()?,
+        eta: caps.name("eta").and_then(|eta| parse_eta(eta.as_str())),
+    }))
+}
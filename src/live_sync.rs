@@ -0,0 +1,198 @@
+use crate::{
+    stats::{percentile, Stats},
+    time::format_elapsed,
+};
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// How many of the slowest blocks are listed.
+const SLOWEST_LISTED: usize = 5;
+
+/// A block the node added to its canonical chain once synced, as logged by
+/// `Block added to canonical chain` lines.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CanonicalBlock {
+    pub number: u64,
+    pub hash: Option<String>,
+    pub timestamp: SystemTime,
+    pub txs: Option<u64>,
+    pub gas_used: Option<u64>,
+    /// Time the block took to process, in seconds.
+    pub elapsed: f64,
+}
+
+/// Parses an amount of gas as logged by reth, either a plain number or
+/// formatted like `12.34 Mgas` or `12.34Mgas`.
+pub(crate) fn parse_gas(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: f64 = amount.parse().ok()?;
+    let scale = match unit.trim_start() {
+        "" | "gas" => 1.0,
+        "Kgas" => 1e3,
+        "Mgas" => 1e6,
+        "Ggas" => 1e9,
+        _ => return None,
+    };
+    Some((amount * scale).round() as u64)
+}
+
+/// Block processing once the node follows the tip of the chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveSyncSummary {
+    pub blocks: usize,
+    pub first_block: u64,
+    pub last_block: u64,
+    pub txs: u64,
+    pub gas_used: u64,
+    /// Gas processed per second of block processing.
+    pub gas_per_second: Option<f64>,
+    /// Time blocks took to process, in seconds.
+    pub latency: Stats,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    /// The slowest blocks, slowest first.
+    pub slowest: Vec<CanonicalBlock>,
+}
+
+impl LiveSyncSummary {
+    /// Summarises the blocks, `None` if there are none.
+    pub fn new(blocks: &[CanonicalBlock]) -> Option<Self> {
+        let first_block = blocks.iter().map(|block| block.number).min()?;
+        let last_block = blocks.iter().map(|block| block.number).max()?;
+
+        let mut latency = Stats::default();
+        for block in blocks {
+            latency.update(block.elapsed);
+        }
+        let mut elapsed: Vec<f64> = blocks.iter().map(|block| block.elapsed).collect();
+        elapsed.sort_by(f64::total_cmp);
+
+        let gas_used: u64 = blocks.iter().filter_map(|block| block.gas_used).sum();
+        let gas_elapsed: f64 = blocks
+            .iter()
+            .filter(|block| block.gas_used.is_some())
+            .map(|block| block.elapsed)
+            .sum();
+
+        let mut slowest = blocks.to_vec();
+        slowest.sort_by(|a, b| b.elapsed.total_cmp(&a.elapsed));
+        slowest.truncate(SLOWEST_LISTED);
+
+        Some(LiveSyncSummary {
+            blocks: blocks.len(),
+            first_block,
+            last_block,
+            txs: blocks.iter().filter_map(|block| block.txs).sum(),
+            gas_used,
+            gas_per_second: (gas_elapsed > 0.0).then(|| gas_used as f64 / gas_elapsed),
+            latency,
+            p50: percentile(&elapsed, 50.0),
+            p90: percentile(&elapsed, 90.0),
+            p99: percentile(&elapsed, 99.0),
+            slowest,
+        })
+    }
+
+    pub(crate) fn print<W: std::io::Write>(&self, writer: &mut W) {
+        writeln!(writer, "Live Sync:").unwrap();
+        writeln!(
+            writer,
+            "  Blocks: {} ({}-{}), {} transactions",
+            self.blocks, self.first_block, self.last_block, self.txs
+        )
+        .unwrap();
+        if let Some(gas_per_second) = self.gas_per_second {
+            writeln!(
+                writer,
+                "  Gas: {:.2} Mgas, {:.2} Mgas/s",
+                self.gas_used as f64 / 1e6,
+                gas_per_second / 1e6
+            )
+            .unwrap();
+        }
+        writeln!(
+            writer,
+            "  Block Latency: mean {}, std {}, min {}, max {}, p50 {}, p90 {}, p99 {}",
+            format_elapsed(self.latency.mean),
+            format_elapsed(self.latency.std_dev()),
+            format_elapsed(self.latency.min),
+            format_elapsed(self.latency.max),
+            format_elapsed(self.p50),
+            format_elapsed(self.p90),
+            format_elapsed(self.p99)
+        )
+        .unwrap();
+
+        writeln!(writer, "  Slowest Blocks:").unwrap();
+        for block in &self.slowest {
+            let mut description = format!("    {}", block.number);
+            if let Some(hash) = &block.hash {
+                description.push_str(&format!(" ({hash})"));
+            }
+            description.push_str(&format!(": {}", format_elapsed(block.elapsed)));
+            if let Some(txs) = block.txs {
+                description.push_str(&format!(", {txs} txs"));
+            }
+            if let Some(gas_used) = block.gas_used {
+                description.push_str(&format!(", {:.2} Mgas", gas_used as f64 / 1e6));
+            }
+            writeln!(writer, "{description}").unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_gas() {
+        assert_eq!(parse_gas("12.34 Mgas"), Some(12_340_000));
+        assert_eq!(parse_gas("1.5Ggas"), Some(1_500_000_000));
+        assert_eq!(parse_gas("21000"), Some(21_000));
+        assert_eq!(parse_gas("12 Tgas"), None);
+    }
+
+    #[test]
+    fn test_live_sync_summary() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_718_000_000);
+        let blocks: Vec<CanonicalBlock> = [(100, 0.05), (101, 0.25), (102, 0.1), (103, 0.1)]
+            .into_iter()
+            .map(|(number, elapsed)| CanonicalBlock {
+                number,
+                hash: Some(format!("0x{number:x}")),
+                timestamp: start + Duration::from_secs(12 * (number - 100)),
+                txs: Some(100),
+                gas_used: Some(10_000_000),
+                elapsed,
+            })
+            .collect();
+
+        let summary = LiveSyncSummary::new(&blocks).unwrap();
+        assert_eq!((summary.first_block, summary.last_block), (100, 103));
+        assert_eq!(summary.gas_per_second, Some(80_000_000.0));
+
+        let mut output = Vec::new();
+        summary.print(&mut output);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Live Sync:\n  \
+             Blocks: 4 (100-103), 400 transactions\n  \
+             Gas: 40.00 Mgas, 80.00 Mgas/s\n  \
+             Block Latency: mean 125.0ms, std 75.0ms, min 50.0ms, max 250.0ms, p50 100.0ms, \
+             p90 250.0ms, p99 250.0ms\n  \
+             Slowest Blocks:\n    \
+             101 (0x65): 250.0ms, 100 txs, 10.00 Mgas\n    \
+             102 (0x66): 100.0ms, 100 txs, 10.00 Mgas\n    \
+             103 (0x67): 100.0ms, 100 txs, 10.00 Mgas\n    \
+             100 (0x64): 50.0ms, 100 txs, 10.00 Mgas\n"
+        );
+        assert!(LiveSyncSummary::new(&[]).is_none());
+    }
+}
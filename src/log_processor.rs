@@ -1,82 +1,352 @@
 use crate::{
-    pipeline::Pipeline,
-    time::{extract_timestamp, format_duration},
+    abort::Abort,
+    engine::{EngineCall, EngineSummary, PayloadStatus},
+    event::Event,
+    format::EntryMetadata,
+    issues::IssueGroup,
+    line_parser::{LineParser, ParsedLine},
+    live_sync::{CanonicalBlock, LiveSyncSummary},
+    pipeline::{Interruption, Pipeline, ProgressSample},
+    session::Session,
+    state_root::{StateRootSummary, StateRootValidation},
+    time::{format_duration, TimestampSource},
 };
 use eyre::Result;
-use regex::Regex;
-use std::{collections::HashMap, time::Duration};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// Everything the processor has learnt from the lines read so far, which is
+/// persisted between incremental runs.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ProcessorState {
+    pipelines: Vec<Pipeline>,
+    current_pipeline: Option<Pipeline>,
+    timestamp_reference: Option<SystemTime>,
+    sessions: Vec<Session>,
+    current_session: usize,
+    canonical_blocks: Vec<CanonicalBlock>,
+    engine_calls: Vec<EngineCall>,
+    issues: Vec<IssueGroup>,
+}
 
 pub struct LogProcessor {
-    regexes: HashMap<String, Regex>,
+    parser: LineParser,
     pub pipelines: Vec<Pipeline>,
     pub current_pipeline: Option<Pipeline>,
+    /// Lifetimes of the reth process the log covers.
+    pub sessions: Vec<Session>,
+    /// Index of the session of the process which wrote the latest event.
+    current_session: usize,
+    /// Blocks added to the canonical chain once the node followed the tip.
+    pub canonical_blocks: Vec<CanonicalBlock>,
+    /// Engine API calls from the consensus layer, in the order they came in.
+    pub engine_calls: Vec<EngineCall>,
+    /// WARN and ERROR lines, grouped by target and message template.
+    pub issues: Vec<IssueGroup>,
+    /// Whether the lines following the report of an abnormal termination
+    /// are being added to it.
+    reading_report: bool,
 }
 
 impl LogProcessor {
     pub fn new() -> Result<Self> {
-        let regexes = vec![
-            (
-                "start".to_string(),
-                Regex::new(
-                    r"Preparing stage pipeline_stages=\d+/\d+ stage=(\w+) checkpoint=\d+ target=",
-                )?,
-            ),
-            (
-                "end".to_string(),
-                Regex::new(
-                    r"Finished stage pipeline_stages=\d+/\d+ stage=(\w+) checkpoint=\d+ target=",
-                )?,
-            ),
-            (
-                "state_root".to_string(),
-                Regex::new(r"Validated state root.*elapsed=(\d+\.\d+)(ms|s)")?,
-            ),
-        ]
-        .into_iter()
-        .collect();
-
         Ok(LogProcessor {
-            regexes,
+            parser: LineParser::new()?,
             pipelines: Vec::new(),
             current_pipeline: None,
+            sessions: Vec::new(),
+            current_session: 0,
+            canonical_blocks: Vec::new(),
+            engine_calls: Vec::new(),
+            issues: Vec::new(),
+            reading_report: false,
         })
     }
 
+    /// Sets the order in which the sources of line timestamps are tried.
+    pub fn set_timestamp_priority(&mut self, priority: Vec<TimestampSource>) {
+        self.parser.set_timestamp_priority(priority);
+    }
+
+    pub(crate) fn state(&self) -> ProcessorState {
+        ProcessorState {
+            pipelines: self.pipelines.clone(),
+            current_pipeline: self.current_pipeline.clone(),
+            timestamp_reference: self.parser.timestamp_reference(),
+            sessions: self.sessions.clone(),
+            current_session: self.current_session,
+            canonical_blocks: self.canonical_blocks.clone(),
+            engine_calls: self.engine_calls.clone(),
+            issues: self.issues.clone(),
+        }
+    }
+
+    /// Continues from a state saved by a previous run.
+    pub(crate) fn restore(&mut self, state: ProcessorState) {
+        self.pipelines = state.pipelines;
+        self.current_pipeline = state.current_pipeline;
+        self.parser
+            .set_timestamp_reference(state.timestamp_reference);
+        self.sessions = state.sessions;
+        self.current_session = state.current_session;
+        self.canonical_blocks = state.canonical_blocks;
+        self.engine_calls = state.engine_calls;
+        self.issues = state.issues;
+    }
+
+    /// The parser of the lines, which parallel parsing clones for each chunk.
+    pub(crate) fn parser(&self) -> &LineParser {
+        &self.parser
+    }
+
+    /// Continues inferring the years of syslog timestamps from `reference`,
+    /// the last timestamp parsed in parallel.
+    pub(crate) fn set_timestamp_reference(&mut self, reference: SystemTime) {
+        self.parser.set_timestamp_reference(Some(reference));
+    }
+
+    #[cfg(test)]
     pub fn process_line(&mut self, line: &str) -> Result<()> {
-        if let Some(start_caps) = self.regexes["start"].captures(line) {
-            let stage_name = start_caps.get(1).unwrap().as_str();
+        self.process_entry(line, &EntryMetadata::default())
+    }
+
+    /// Processes a text or JSON line read from a log collector.
+    pub(crate) fn process_entry(&mut self, line: &str, metadata: &EntryMetadata) -> Result<()> {
+        let parsed = self.parser.parse(line, metadata, self.reading_report);
+        self.apply(line, metadata, parsed)
+    }
 
-            if self.current_pipeline.is_none() || self.is_first_stage(stage_name) {
-                self.init_pipeline();
+    /// Applies what was parsed from a line to the pipelines and sessions,
+    /// which must be done in the order of the log.
+    pub(crate) fn apply(
+        &mut self,
+        line: &str,
+        metadata: &EntryMetadata,
+        parsed: ParsedLine,
+    ) -> Result<()> {
+        if self.reading_report {
+            if let Some(message) = &parsed.continuation {
+                if self.continue_report(message, parsed.pid) {
+                    return Ok(());
+                }
             }
+            self.reading_report = false;
+        }
+        if let Some((issue, template)) = parsed.issue {
+            let stage = self
+                .current_pipeline
+                .as_ref()
+                .and_then(Pipeline::running_stage);
+            IssueGroup::record(&mut self.issues, issue, template, stage, line);
+        }
 
-            if let Some(ref mut pipeline) = self.current_pipeline {
-                let timestamp = extract_timestamp(line)?;
-                pipeline.record_stage_start(stage_name, timestamp);
+        if let Some(event) = parsed.event? {
+            let pid = match event {
+                Event::Abort {
+                    supervisor: true, ..
+                } => None,
+                _ => parsed.pid,
+            };
+            self.switch_session(&event, pid);
+            let timestamp = event.timestamp();
+            self.handle_event(event, metadata)?;
+            self.sessions[self.current_session].record(pid, timestamp);
+        }
+
+        Ok(())
+    }
+
+    /// Adds a line to the report of the abnormal termination of the current
+    /// pipeline, returning whether it continued it.
+    fn continue_report(&mut self, message: &str, pid: Option<u32>) -> bool {
+        if self
+            .sessions
+            .get(self.current_session)
+            .map(|session| session.pid)
+            != Some(pid)
+        {
+            return false;
+        }
+        match self
+            .current_pipeline
+            .as_mut()
+            .and_then(|pipeline| pipeline.abort.as_mut())
+        {
+            Some(abort) => {
+                abort.append(message);
+                true
             }
+            None => false,
         }
+    }
 
-        if let Some(end_caps) = self.regexes["end"].captures(line) {
-            if let Some(ref mut pipeline) = self.current_pipeline {
-                let stage_name = end_caps.get(1).unwrap().as_str();
-                let timestamp = extract_timestamp(line)?;
-                pipeline.record_stage_end(stage_name, timestamp)?;
+    /// Switches session on a startup banner or a line of another process.
+    fn switch_session(&mut self, event: &Event, pid: Option<u32>) {
+        let startup = matches!(event, Event::Startup { .. });
+        if let Some(session) = self.sessions.get(self.current_session) {
+            if !startup && !session.is_other_process(pid) {
+                return;
+            }
+            self.close_pipeline(Interruption::Restart);
+        }
+
+        let previous = match (startup, pid) {
+            (false, Some(pid)) => self
+                .sessions
+                .iter()
+                .rposition(|session| session.pid == Some(pid)),
+            _ => None,
+        };
+        match previous {
+            Some(index) => {
+                self.current_session = index;
+                self.reopen_pipeline();
+            }
+            None => {
+                self.sessions.push(Session::new(pid));
+                self.current_session = self.sessions.len() - 1;
             }
         }
+    }
+
+    /// Continues the last pipeline of the current session, which was closed
+    /// when lines of another process came in between.
+    fn reopen_pipeline(&mut self) {
+        if let Some(index) = self
+            .pipelines
+            .iter()
+            .rposition(|pipeline| pipeline.session == self.current_session)
+        {
+            let mut pipeline = self.pipelines.remove(index);
+            pipeline.interruption = None;
+            self.current_pipeline = Some(pipeline);
+        }
+    }
+
+    /// Timestamp of the latest event of the current session, up to which
+    /// interrupted stages are timed.
+    fn last_timestamp(&self) -> Option<SystemTime> {
+        self.sessions
+            .get(self.current_session)
+            .and_then(|session| session.end)
+    }
+
+    fn handle_event(&mut self, event: Event, metadata: &EntryMetadata) -> Result<()> {
+        match event {
+            Event::StageStart {
+                stage,
+                timestamp,
+                checkpoint,
+                pipeline_stages,
+            } => {
+                let starts_pipeline = match &self.current_pipeline {
+                    Some(pipeline) => pipeline.is_followed_by(&stage, pipeline_stages),
+                    None => true,
+                };
+                if starts_pipeline {
+                    self.init_pipeline(Interruption::NewPipeline);
+                }
+
+                if let Some(ref mut pipeline) = self.current_pipeline {
+                    if pipeline.origin.is_none() {
+                        pipeline.origin = metadata.origin();
+                    }
+                    if pipeline_stages.is_some() {
+                        pipeline.pipeline_stages = pipeline_stages;
+                    }
+                    pipeline.record_stage_start(&stage, timestamp, checkpoint);
+                }
+            }
+            Event::StageEnd {
+                stage,
+                timestamp,
+                checkpoint,
+                done,
+                progress,
+            } => {
+                if let Some(ref mut pipeline) = self.current_pipeline {
+                    if let Some(progress) = progress {
+                        pipeline.record_stage_progress(
+                            &stage,
+                            ProgressSample::new(timestamp, checkpoint, progress),
+                        );
+                    }
+                    pipeline.record_stage_end(&stage, timestamp, checkpoint, done)?;
+                }
+            }
+            Event::Status { sample, stage } => {
+                self.sessions[self.current_session].status.push(sample);
+                if let Some(ref mut pipeline) = self.current_pipeline {
+                    pipeline.status.push(sample);
+                    if let Some(stage) = stage {
+                        pipeline.record_stage_progress(
+                            &stage.stage,
+                            ProgressSample::new(sample.timestamp, stage.checkpoint, stage.progress),
+                        );
+                    }
+                }
+            }
+            Event::UnwindStart {
+                stage,
+                timestamp,
+                checkpoint,
+            } => {
+                if self.current_pipeline.is_none() {
+                    self.init_pipeline(Interruption::NewPipeline);
+                }
 
-        if let Some(caps) = self.regexes["state_root"].captures(line) {
-            if let (Some(matched_value), Some(unit)) = (caps.get(1), caps.get(2)) {
-                let mut elapsed: f64 = matched_value.as_str().parse()?;
-                if unit.as_str() == "ms" {
-                    elapsed /= 1000.0;
+                if let Some(ref mut pipeline) = self.current_pipeline {
+                    pipeline.record_unwind_start(&stage, timestamp, checkpoint);
+                }
+            }
+            Event::UnwindEnd {
+                stage,
+                timestamp,
+                checkpoint,
+            } => {
+                if let Some(ref mut pipeline) = self.current_pipeline {
+                    pipeline.record_unwind_end(&stage, timestamp, checkpoint);
                 }
+            }
+            Event::BlockAdded { block } => {
+                EngineCall::resolve(
+                    &mut self.engine_calls,
+                    block.hash.as_deref(),
+                    block.timestamp,
+                    PayloadStatus::Valid,
+                );
+                self.canonical_blocks.push(block);
+            }
+            Event::EngineCall { call } => EngineCall::record(&mut self.engine_calls, call),
+            Event::Startup { .. } => {}
+            Event::Abort {
+                timestamp,
+                reason,
+                report,
+                supervisor,
+            } => {
+                let last_timestamp = self.last_timestamp();
+                match &mut self.current_pipeline {
+                    Some(pipeline) => {
+                        let abort =
+                            Abort::new(reason, timestamp, pipeline.running_stage(), &report);
+                        // What follows the report of the supervisor is
+                        // written by other processes.
+                        self.reading_report =
+                            pipeline.record_abort(abort, last_timestamp) && !supervisor;
+                    }
+                    None => warn!("reth terminated outside of a pipeline: {report}"),
+                }
+            }
+            Event::StateRoot { block, elapsed, .. } => {
                 if self.current_pipeline.is_none() {
-                    self.init_pipeline();
+                    self.init_pipeline(Interruption::NewPipeline);
                 }
 
                 let current_pipeline = &mut self.current_pipeline;
                 if let Some(ref mut pipeline) = current_pipeline {
-                    pipeline.update_stats("state_root", elapsed);
+                    pipeline.record_state_root(StateRootValidation { block, elapsed });
                 }
             }
         }
@@ -84,18 +354,18 @@ impl LogProcessor {
         Ok(())
     }
 
-    fn is_first_stage(&self, stage_name: &str) -> bool {
-        stage_name == "Headers"
-    }
-
     pub fn print_summary<W: std::io::Write>(&self, writer: &mut W) {
         let pipelines = &self.pipelines;
         let mut total_duration = Duration::new(0, 0);
 
         for (index, pipeline) in pipelines.iter().enumerate() {
             pipeline.print_summary(index, writer);
-            total_duration += pipeline.durations.values().sum::<Duration>();
+            total_duration += pipeline.finished_duration();
         }
+        self.print_state_roots(pipelines.iter(), writer);
+        self.print_live_sync(writer);
+        self.print_engine_calls(writer);
+        self.print_sessions(pipelines.iter(), writer);
 
         writeln!(
             writer,
@@ -105,24 +375,146 @@ impl LogProcessor {
         .unwrap();
     }
 
-    fn init_pipeline(&mut self) {
-        if let Some(pipeline) = self.current_pipeline.take() {
-            self.pipelines.push(pipeline);
+    /// Prints the summary of a log which is still being written, timing
+    /// unfinished stages up to `now`.
+    pub fn print_live_summary<W: std::io::Write>(&self, writer: &mut W, now: SystemTime) {
+        let mut total_duration = Duration::new(0, 0);
+
+        for (index, pipeline) in self.pipelines.iter().enumerate() {
+            pipeline.print_summary(index, writer);
+            total_duration += pipeline.finished_duration();
+        }
+        if let Some(pipeline) = &self.current_pipeline {
+            pipeline.print_live_summary(self.pipelines.len(), writer, now);
+            total_duration += pipeline.finished_duration();
+        }
+        self.print_state_roots(self.pipelines.iter().chain(&self.current_pipeline), writer);
+        self.print_live_sync(writer);
+        self.print_engine_calls(writer);
+        self.print_sessions(self.pipelines.iter().chain(&self.current_pipeline), writer);
+
+        writeln!(
+            writer,
+            "Total Aggregate Duration: {}",
+            format_duration(&total_duration)
+        )
+        .unwrap();
+    }
+
+    /// Prints the WARN and ERROR lines grouped by target and message
+    /// template, if any.
+    pub fn print_issues<W: std::io::Write>(&self, writer: &mut W) {
+        IssueGroup::print_summary(&self.issues, writer);
+    }
+
+    /// Summarises the state root validations of all pipelines together, when
+    /// they were logged during several.
+    fn print_state_roots<'p, W: std::io::Write>(
+        &self,
+        pipelines: impl Iterator<Item = &'p Pipeline> + Clone,
+        writer: &mut W,
+    ) {
+        let validated = pipelines
+            .clone()
+            .filter(|pipeline| !pipeline.state_roots.is_empty())
+            .count();
+        if validated > 1 {
+            if let Some(summary) =
+                StateRootSummary::new(pipelines.flat_map(|pipeline| &pipeline.state_roots))
+            {
+                summary.print("State Root (all pipelines)", "", writer);
+            }
+        }
+    }
+
+    /// Summarises how blocks were processed once the node followed the tip,
+    /// if it did.
+    fn print_live_sync<W: std::io::Write>(&self, writer: &mut W) {
+        if let Some(summary) = LiveSyncSummary::new(&self.canonical_blocks) {
+            summary.print(writer);
+        }
+    }
+
+    /// Summarises the Engine API calls of the consensus layer, if any.
+    fn print_engine_calls<W: std::io::Write>(&self, writer: &mut W) {
+        if let Some(summary) = EngineSummary::new(&self.engine_calls) {
+            summary.print(writer);
+        }
+    }
+
+    /// Lists the sessions when the log covers several, along with the
+    /// downtime between them.
+    fn print_sessions<'p, W: std::io::Write>(
+        &self,
+        pipelines: impl Iterator<Item = &'p Pipeline>,
+        writer: &mut W,
+    ) {
+        if self.sessions.len() > 1 {
+            let pipeline_sessions: Vec<usize> =
+                pipelines.map(|pipeline| pipeline.session).collect();
+            Session::print_summary(&self.sessions, &pipeline_sessions, writer);
         }
-        self.current_pipeline = Some(Pipeline::new());
+    }
+
+    /// Moves the current pipeline to the finished ones, in the order they
+    /// started, recording why the stages still running were interrupted.
+    fn close_pipeline(&mut self, reason: Interruption) {
+        let Some(mut pipeline) = self.current_pipeline.take() else {
+            return;
+        };
+        if let Some(timestamp) = self
+            .sessions
+            .get(pipeline.session)
+            .and_then(|session| session.end)
+        {
+            pipeline.interrupt(timestamp, reason);
+        }
+
+        let index = match pipeline.start_time() {
+            Some(start_time) => self.pipelines.partition_point(|other| {
+                other.start_time().is_none_or(|other| other <= start_time)
+            }),
+            None => self.pipelines.len(),
+        };
+        self.pipelines.insert(index, pipeline);
+    }
+
+    /// Closes the last pipeline once the whole log was read, warning about
+    /// the pipelines which finished fewer stages than they declared.
+    pub(crate) fn finish(&mut self) {
+        self.close_pipeline(Interruption::EndOfLog);
+        for (index, pipeline) in self.pipelines.iter().enumerate() {
+            if let (Some(declared), finished) = pipeline.finished_stages() {
+                if finished < declared {
+                    warn!(
+                        "Pipeline {} finished {finished} of the {declared} stages it declared",
+                        index + 1
+                    );
+                }
+            }
+        }
+    }
+
+    fn init_pipeline(&mut self, reason: Interruption) {
+        self.close_pipeline(reason);
+        let mut pipeline = Pipeline::new();
+        pipeline.session = self.current_session;
+        self.current_pipeline = Some(pipeline);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::time::parse_rfc3339;
 
     #[test]
     fn test_log_processor_new() {
         let processor = LogProcessor::new().unwrap();
-        assert!(processor.regexes.contains_key("start"));
-        assert!(processor.regexes.contains_key("end"));
-        assert!(processor.regexes.contains_key("state_root"));
+        assert!(processor.parser().is_relevant("Preparing stage"));
+        assert!(processor.parser().is_relevant("Finished stage"));
+        assert!(processor.parser().is_relevant("Validated state root"));
+        assert!(processor.pipelines.is_empty());
     }
 
     #[test]
@@ -164,10 +556,14 @@ mod tests {
     #[test]
     fn test_process_line_state_root() {
         let mut processor = LogProcessor::new().unwrap();
-        let line = "Validated state root elapsed=2.5s";
+        let line = "2024-06-07T09:06:21.873354Z DEBUG Validated state root elapsed=2.5s";
 
         processor.process_line(line).unwrap();
 
+        assert_eq!(
+            processor.sessions[0].end,
+            Some(parse_rfc3339("2024-06-07T09:06:21.873354Z").unwrap())
+        );
         let current_pipeline = processor.current_pipeline;
 
         assert!(current_pipeline.is_some());
@@ -177,4 +573,407 @@ mod tests {
             .stats
             .contains_key("state_root"));
     }
+
+    #[test]
+    fn test_state_root_summary() {
+        let mut processor = LogProcessor::new().unwrap();
+        processor.process_line("2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/1 stage=Finish checkpoint=0 target=100").unwrap();
+        processor
+            .process_line(
+                "2024-06-07T09:05:21.873354Z DEBUG Validated state root number=101 elapsed=120.5ms",
+            )
+            .unwrap();
+        processor
+            .process_line(
+                "2024-06-07T09:05:22.873354Z DEBUG Validated state root number=102 elapsed=1.5s",
+            )
+            .unwrap();
+        processor.process_line("2024-06-07T09:05:23.873354Z  INFO Finished stage pipeline_stages=1/1 stage=Finish checkpoint=100 target=100").unwrap();
+        processor.process_line("2024-06-07T09:06:20.873354Z  INFO Preparing stage pipeline_stages=1/1 stage=Finish checkpoint=100 target=200").unwrap();
+        processor
+            .process_line(
+                "2024-06-07T09:06:21.873354Z DEBUG Validated state root number=201 elapsed=80ms",
+            )
+            .unwrap();
+        processor.finish();
+
+        assert_eq!(
+            processor.pipelines[0].state_roots,
+            vec![
+                StateRootValidation {
+                    block: Some(101),
+                    elapsed: 0.1205
+                },
+                StateRootValidation {
+                    block: Some(102),
+                    elapsed: 1.5
+                }
+            ]
+        );
+
+        let mut output = Vec::new();
+        processor.print_summary(&mut output);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(
+            "  State Root: 2 validations, mean 810.2ms, std 689.8ms, min 120.5ms, max 1.50s, \
+             p50 120.5ms, p90 1.50s, p99 1.50s\n    \
+             1 slower than 1.00s: block 102 (1.50s)\n"
+        ));
+        assert!(output.contains(
+            "State Root (all pipelines): 3 validations, mean 566.8ms, std 660.1ms, min 80.0ms, \
+             max 1.50s, p50 120.5ms, p90 1.50s, p99 1.50s\n  \
+             1 slower than 1.00s: block 102 (1.50s)\n"
+        ));
+    }
+
+    #[test]
+    fn test_live_sync() {
+        let mut processor = LogProcessor::new().unwrap();
+        processor.process_line("2024-06-19T09:21:22.123456Z  INFO Block added to canonical chain number=20124789 hash=0xf0a3 peers=88 txs=150 gas=12.34 Mgas gas_throughput=401.12 Mgas/second full=41.1% base_fee=3.45gwei blobs=0 excess_blobs=0 elapsed=30.712ms").unwrap();
+        processor.process_line("2024-06-19T09:21:34.123456Z  INFO Block added to canonical chain number=20124790 hash=0x9b1c peers=88 txs=10 mgas=1.000 full=3.3% base_fee=3.41gwei elapsed=10ms").unwrap();
+        processor.finish();
+
+        assert!(processor.pipelines.is_empty());
+        assert_eq!(processor.canonical_blocks.len(), 2);
+        assert_eq!(processor.canonical_blocks[1].gas_used, Some(1_000_000));
+
+        let mut output = Vec::new();
+        processor.print_summary(&mut output);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("  Blocks: 2 (20124789-20124790), 160 transactions\n"));
+        assert!(output.contains("    20124789 (0xf0a3): 30.7ms, 150 txs, 12.34 Mgas\n"));
+    }
+
+    #[test]
+    fn test_engine_calls() {
+        let mut processor = LogProcessor::new().unwrap();
+        processor.process_line("2024-06-19T09:21:22.000000Z  INFO Received block from consensus engine number=20124789 hash=0xf0a3").unwrap();
+        processor.process_line("2024-06-19T09:21:22.250000Z  INFO Block added to canonical chain number=20124789 hash=0xf0a3 peers=88 txs=150 gas=12.34 Mgas gas_throughput=401.12 Mgas/second full=41.1% base_fee=3.45gwei blobs=0 excess_blobs=0 elapsed=30.712ms").unwrap();
+        processor.process_line("2024-06-19T09:21:22.500000Z  INFO Forkchoice updated head_block_hash=0xf0a3 safe_block_hash=0x11aa finalized_block_hash=0x22bb").unwrap();
+        processor.process_line("2024-06-19T09:23:34.000000Z  INFO Received block from consensus engine number=20124790 hash=0x9b1c").unwrap();
+        processor.process_line("2024-06-19T09:23:34.500000Z  WARN Invalid block error on new payload invalid_hash=0x9b1c invalid_number=Some(20124790) validation_err=state root mismatch").unwrap();
+        processor.finish();
+
+        assert_eq!(processor.engine_calls.len(), 3);
+        assert_eq!(processor.engine_calls[0].elapsed, Some(0.25));
+
+        let mut output = Vec::new();
+        processor.print_summary(&mut output);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(
+            "Engine API:\n  \
+             forkchoiceUpdated: 1 (VALID 1)\n  \
+             newPayload: 2 (VALID 1, INVALID 1), latency p50 250.0ms, p90 500.0ms, p99 500.0ms, max 500.0ms\n  \
+             1 gap without consensus layer messages longer than 1m 0s, 2m 11s in total:\n    \
+             2024-06-19 09:21:22 - 2024-06-19 09:23:34: 2m 11s\n"
+        ));
+    }
+
+    #[test]
+    fn test_issues() {
+        let mut processor = LogProcessor::new().unwrap();
+        processor.process_line("2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=0 target=None").unwrap();
+        processor.process_line("2024-06-07T09:05:30.000000Z  WARN net::session: Session error peer_id=0x01 err=timeout").unwrap();
+        processor.process_line("2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=1000 target=None").unwrap();
+        processor.process_line(r#"{"timestamp":"2024-06-07T09:06:30.000000Z","level":"WARN","fields":{"message":"Session error","peer_id":"0x02","err":"timeout"},"target":"net::session"}"#).unwrap();
+        processor
+            .process_line("2024-06-07T09:06:40.000000Z ERROR Database error code=12")
+            .unwrap();
+        processor
+            .process_line("2024-06-07T09:06:50.000000Z  INFO Received message kind=ERROR_REPORT")
+            .unwrap();
+        processor.finish();
+
+        let mut output = Vec::new();
+        processor.print_issues(&mut output);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Warnings and Errors: 1 error, 2 warnings\n  \
+             1x ERROR Database error code=<n>\n    \
+             first 2024-06-07 09:06:40, last 2024-06-07 09:06:40, outside of stages\n    \
+             sample: 2024-06-07T09:06:40.000000Z ERROR Database error code=12\n  \
+             2x WARN net::session: Session error peer_id=<peer> err=timeout\n    \
+             first 2024-06-07 09:05:30, last 2024-06-07 09:06:30, during Headers\n    \
+             sample: 2024-06-07T09:05:30.000000Z  WARN net::session: Session error peer_id=0x01 err=timeout\n"
+        );
+    }
+
+    #[test]
+    fn test_pipeline_boundaries() {
+        let mut processor = LogProcessor::new().unwrap();
+        // A pipeline which doesn't start with Headers, running a stage twice.
+        processor.process_line("2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/3 stage=Bodies checkpoint=0 target=2000").unwrap();
+        processor.process_line("2024-06-07T09:05:21.873354Z  INFO Committed stage progress pipeline_stages=1/3 stage=Bodies checkpoint=1000 target=2000").unwrap();
+        processor.process_line("2024-06-07T09:05:22.873354Z  INFO Preparing stage pipeline_stages=1/3 stage=Bodies checkpoint=1000 target=2000").unwrap();
+        processor.process_line("2024-06-07T09:05:23.873354Z  INFO Finished stage pipeline_stages=1/3 stage=Bodies checkpoint=2000 target=2000").unwrap();
+        processor.process_line("2024-06-07T09:05:24.873354Z  INFO Preparing stage pipeline_stages=2/3 stage=Execution checkpoint=0 target=2000").unwrap();
+        processor.process_line("2024-06-07T09:05:25.873354Z  INFO Finished stage pipeline_stages=2/3 stage=Execution checkpoint=2000 target=2000").unwrap();
+        // The index going back starts a new pipeline.
+        processor.process_line("2024-06-07T09:05:26.873354Z  INFO Preparing stage pipeline_stages=1/3 stage=Bodies checkpoint=2000 target=3000").unwrap();
+        // So does the number of stages changing.
+        processor.process_line("2024-06-07T09:05:27.873354Z  INFO Preparing stage pipeline_stages=2/12 stage=Bodies checkpoint=2000 target=3000").unwrap();
+        processor.finish();
+
+        assert_eq!(processor.pipelines.len(), 3);
+        assert_eq!(processor.pipelines[0].finished_stages(), (Some(3), 2));
+        assert_eq!(processor.pipelines[1].finished_stages(), (Some(3), 0));
+        assert_eq!(processor.pipelines[2].finished_stages(), (Some(12), 0));
+    }
+
+    #[test]
+    fn test_interrupted_stages() {
+        let mut processor = LogProcessor::new().unwrap();
+        processor.process_line("2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=0 target=None").unwrap();
+        processor.process_line("2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=1000 target=None").unwrap();
+        processor.process_line("2024-06-07T09:06:20.873354Z  INFO Preparing stage pipeline_stages=2/12 stage=Bodies checkpoint=0 target=1000").unwrap();
+        processor.process_line("2024-06-07T09:08:20.873354Z  INFO Status connected_peers=10 stage=Bodies checkpoint=500 target=1000 stage_progress=50.00%").unwrap();
+        // reth starts a new pipeline, without restarting.
+        processor.process_line("2024-06-07T10:00:00.000000Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=1000 target=None").unwrap();
+        processor.process_line("2024-06-07T10:00:10.000000Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=1000 target=None").unwrap();
+        processor.process_line("2024-06-07T10:00:10.000000Z  INFO Preparing stage pipeline_stages=2/12 stage=Bodies checkpoint=500 target=1000").unwrap();
+        processor.process_line("2024-06-07T10:00:40.000000Z  INFO Status connected_peers=10 stage=Bodies checkpoint=700 target=1000 stage_progress=70.00%").unwrap();
+        processor
+            .process_line(
+                "thread 'tokio-runtime-worker' panicked at crates/stages/src/lib.rs:10:5:",
+            )
+            .unwrap();
+        processor
+            .process_line("called `Option::unwrap()` on a `None` value")
+            .unwrap();
+        processor
+            .process_line(
+                "note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace",
+            )
+            .unwrap();
+        processor.process_line("2024-06-07T11:00:00.000000Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=700 target=None").unwrap();
+        processor.finish();
+
+        let mut output = Vec::new();
+        processor.print_summary(&mut output);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Pipeline 1:\n  \
+             Stage 001 - Headers: 1m 0s (blocks 0-1000, 16.7 blocks/s)\n  \
+             Stage 002 - Bodies: 2m 0s (interrupted by a new pipeline, 50.00%)\n  \
+             Node Health: 1 status report, peers 10-10 (10.0 on average)\n  \
+             Total Pipeline Duration: 1m 0s\n\
+             Pipeline 2:\n  \
+             Stage 001 - Headers: 10s (blocks 1000-1000)\n  \
+             Stage 002 - Bodies: 30s (interrupted by a panic, 70.00%)\n  \
+             Aborted: panic during Bodies\n    \
+             thread 'tokio-runtime-worker' panicked at crates/stages/src/lib.rs:10:5:\n    \
+             called `Option::unwrap()` on a `None` value\n  \
+             Node Health: 1 status report, peers 10-10 (10.0 on average)\n  \
+             Total Pipeline Duration: 10s\n\
+             Pipeline 3:\n  \
+             Stage 001 - Headers: 0s (in progress at the end of the log)\n  \
+             Total Pipeline Duration: 0s\n\
+             Total Aggregate Duration: 1m 10s\n"
+        );
+    }
+
+    #[test]
+    fn test_aborts() {
+        let mut processor = LogProcessor::new().unwrap();
+        processor.process_line("Jun 07 09:05:20 reth-5 reth-mainnet[100]: 2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=4/12 stage=Execution checkpoint=0 target=1000").unwrap();
+        processor.process_line("Jun 07 09:15:20 reth-5 systemd[1]: reth-mainnet.service: Main process exited, code=killed, status=9/KILL").unwrap();
+        processor.process_line("Jun 07 09:15:20 reth-5 systemd[1]: reth-mainnet.service: A process of this unit has been killed by the OOM killer.").unwrap();
+        processor.process_line("Jun 07 09:15:20 reth-5 systemd[1]: reth-mainnet.service: Failed with result 'oom-kill'.").unwrap();
+        processor.process_line("Jun 07 09:20:00 reth-5 reth-mainnet[200]: 2024-06-07T09:20:00.000000Z  INFO Preparing stage pipeline_stages=4/12 stage=Execution checkpoint=0 target=1000").unwrap();
+        processor
+            .process_line(
+                "Jun 07 09:21:00 reth-5 reth-mainnet[200]: Error: failed to open the database",
+            )
+            .unwrap();
+        processor
+            .process_line("Jun 07 09:21:00 reth-5 reth-mainnet[200]: ")
+            .unwrap();
+        processor
+            .process_line("Jun 07 09:21:00 reth-5 reth-mainnet[200]: Location:")
+            .unwrap();
+        processor
+            .process_line("Jun 07 09:21:00 reth-5 reth-mainnet[200]:     bin/reth/src/main.rs:35:9")
+            .unwrap();
+        processor.process_line("Jun 07 09:21:00 reth-5 systemd[1]: reth-mainnet.service: Main process exited, code=exited, status=1/FAILURE").unwrap();
+        processor.process_line("Jun 07 09:21:00 reth-5 systemd[1]: reth-mainnet.service: Consumed 1h 2min CPU time.").unwrap();
+        processor.finish();
+
+        assert_eq!(processor.sessions.len(), 2);
+        let first = processor.pipelines[0].abort.as_ref().unwrap();
+        assert_eq!(first.reason, Interruption::OutOfMemory);
+        assert_eq!(first.stage.as_deref(), Some("Execution"));
+        assert_eq!(
+            first.report,
+            "reth-mainnet.service: A process of this unit has been killed by the OOM killer."
+        );
+        let second = processor.pipelines[1].abort.as_ref().unwrap();
+        assert_eq!(second.reason, Interruption::ErrorExit);
+        assert_eq!(
+            second.message,
+            ["Location:", "    bin/reth/src/main.rs:35:9"]
+        );
+
+        let mut output = Vec::new();
+        processor.print_summary(&mut output);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(
+            "  Stage 001 - Execution: 9m 59s (interrupted by running out of memory)\n  \
+             Aborted: out of memory during Execution at 2024-06-07 09:15:20\n    \
+             reth-mainnet.service: A process of this unit has been killed by the OOM killer.\n"
+        ));
+        assert!(output.contains(
+            "  Stage 001 - Execution: 1m 0s (interrupted by an error)\n  \
+             Aborted: exit with an error during Execution at 2024-06-07 09:21:00\n    \
+             Error: failed to open the database\n    \
+             Location:\n    \
+             \x20   bin/reth/src/main.rs:35:9\n"
+        ));
+    }
+
+    #[test]
+    fn test_sessions() {
+        let mut processor = LogProcessor::new().unwrap();
+        processor.process_line("Jun 07 09:05:20 reth-5 reth-mainnet[100]: 2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=0 target=None").unwrap();
+        processor.process_line("Jun 07 09:06:20 reth-5 reth-mainnet[100]: 2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=1000 target=None").unwrap();
+        processor.process_line("Jun 07 09:06:20 reth-5 reth-mainnet[100]: 2024-06-07T09:06:20.873354Z  INFO Preparing stage pipeline_stages=2/12 stage=Bodies checkpoint=0 target=1000").unwrap();
+        // Another process starts, the stage running is never finished.
+        processor.process_line("Jun 07 09:10:00 reth-5 reth-mainnet[200]: 2024-06-07T09:10:00.000000Z  INFO Preparing stage pipeline_stages=2/12 stage=Bodies checkpoint=0 target=1000").unwrap();
+        processor.process_line("Jun 07 09:11:00 reth-5 reth-mainnet[200]: 2024-06-07T09:11:00.000000Z  INFO Finished stage pipeline_stages=2/12 stage=Bodies checkpoint=1000 target=1000").unwrap();
+        // A startup banner starts a session even without PIDs.
+        processor
+            .process_line("2024-06-07T09:20:00.000000Z  INFO Starting reth version=\"1.0.0\"")
+            .unwrap();
+        processor.process_line("2024-06-07T09:20:01.000000Z  INFO Preparing stage pipeline_stages=3/12 stage=SenderRecovery checkpoint=0 target=1000").unwrap();
+        processor.finish();
+
+        let sessions: Vec<(Option<u32>, usize)> = processor
+            .pipelines
+            .iter()
+            .map(|pipeline| (processor.sessions[pipeline.session].pid, pipeline.session))
+            .collect();
+        assert_eq!(sessions, [(Some(100), 0), (Some(200), 1), (None, 2)]);
+        assert_eq!(processor.sessions.len(), 3);
+        assert_eq!(
+            processor.sessions[1].downtime_after(&processor.sessions[0]),
+            Some(Duration::from_micros(219_126_646))
+        );
+        assert_eq!(
+            processor.pipelines[0].interruption,
+            Some((
+                parse_rfc3339("2024-06-07T09:06:20.873354Z").unwrap(),
+                Interruption::Restart
+            ))
+        );
+        assert_eq!(
+            processor.pipelines[1].durations["001 - Bodies"],
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn test_interleaved_sessions() {
+        let mut processor = LogProcessor::new().unwrap();
+        processor.process_line("Jun 07 09:05:20 reth-5 reth-mainnet[100]: 2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=0 target=None").unwrap();
+        processor.process_line("Jun 07 08:05:20 reth-5 reth-mainnet[200]: 2024-06-07T08:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=0 target=None").unwrap();
+        processor.process_line("Jun 07 09:06:20 reth-5 reth-mainnet[100]: 2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=1000 target=None").unwrap();
+        processor.finish();
+
+        // The pipelines are ordered by start, and the one of the process whose
+        // lines came back carries on.
+        assert_eq!(processor.sessions.len(), 2);
+        assert_eq!(processor.pipelines.len(), 2);
+        assert_eq!(processor.pipelines[0].session, 1);
+        assert_eq!(processor.pipelines[1].session, 0);
+        assert_eq!(
+            processor.pipelines[1].durations["001 - Headers"],
+            Duration::from_secs(60)
+        );
+        assert!(processor.pipelines[1].interruption.is_none());
+    }
+
+    #[test]
+    fn test_process_line_unwind() {
+        let mut processor = LogProcessor::new().unwrap();
+        processor.process_line("2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=20037711 target=None").unwrap();
+        processor.process_line("2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=20038569 target=None").unwrap();
+        processor.process_line("2024-06-07T09:06:21.873354Z  INFO Unwinding stage=Headers checkpoint=20038569 target=20038500").unwrap();
+        processor
+            .process_line(
+                "2024-06-07T09:06:22.873354Z  INFO Unwound stage stage=Headers checkpoint=20038500",
+            )
+            .unwrap();
+        processor.process_line("2024-06-07T09:06:23.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=20038500 target=None").unwrap();
+
+        assert_eq!(processor.pipelines.len(), 1);
+        let unwinds = &processor.pipelines[0].unwinds;
+        assert_eq!(unwinds.len(), 1);
+        assert_eq!(unwinds[0].target, Some(20038500));
+        assert_eq!(unwinds[0].stages[0].to, Some(20038500));
+        assert_eq!(unwinds[0].duration(), Duration::from_secs(1));
+        assert!(processor.current_pipeline.unwrap().unwinds.is_empty());
+    }
+
+    #[test]
+    fn test_print_live_summary() {
+        let mut processor = LogProcessor::new().unwrap();
+        processor.process_line("2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=20037711 target=None").unwrap();
+        processor.process_line("2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=20038569 target=None").unwrap();
+        processor.process_line("2024-06-07T09:06:21.873354Z  INFO Preparing stage pipeline_stages=2/12 stage=Bodies checkpoint=20037711 target=20038569").unwrap();
+        processor.process_line("2024-06-07T09:36:21.873354Z  INFO Status connected_peers=10 freelist=4 stage=Bodies checkpoint=20038000 target=20038569 stage_progress=33.61% stage_eta=1h 2m 3s").unwrap();
+
+        let now = parse_rfc3339("2024-06-07T10:06:21.873354Z").unwrap();
+        let mut output = Vec::new();
+        processor.print_live_summary(&mut output, now);
+
+        let output_str = String::from_utf8(output).unwrap();
+        let expected_output = "Pipeline 1:\n  Stage 001 - Headers: 1m 0s (blocks 20037711-20038569, 14.3 blocks/s)\n  Stage 002 - Bodies: 1h 0m (in progress, 33.61%, ETA 1h 2m)\n  Node Health: 1 status report, peers 10-10 (10.0 on average), freelist 4 -> 4 (+0)\n  Total Pipeline Duration: 1m 0s\nTotal Aggregate Duration: 1m 0s\n";
+        assert_eq!(expected_output, output_str);
+    }
+
+    #[test]
+    fn test_process_entry_uses_metadata() {
+        let mut processor = LogProcessor::new().unwrap();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1717751120);
+        let metadata = EntryMetadata {
+            timestamp: Some(start),
+            hostname: Some("reth-5".to_string()),
+            pid: Some(4009735),
+            identifier: Some("reth-mainnet".to_string()),
+        };
+        processor
+            .process_entry(
+                "INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=20037711 target=None",
+                &metadata,
+            )
+            .unwrap();
+        let metadata = EntryMetadata {
+            timestamp: Some(start + Duration::from_secs(60)),
+            ..metadata
+        };
+        processor
+            .process_entry(
+                "INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=20038569 target=None",
+                &metadata,
+            )
+            .unwrap();
+
+        let pipeline = processor.current_pipeline.unwrap();
+        assert_eq!(
+            pipeline.origin,
+            Some("reth-5 reth-mainnet[4009735]".to_string())
+        );
+        assert_eq!(pipeline.durations["001 - Headers"], Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_process_json_lines() {
+        let mut processor = LogProcessor::new().unwrap();
+        processor.process_line(r#"{"timestamp":"2024-06-07T09:05:20.873354Z","level":"INFO","fields":{"message":"Preparing stage","pipeline_stages":"1/12","stage":"Headers","checkpoint":20037711,"target":"None"},"target":"reth_node_events::node"}"#).unwrap();
+        processor.process_line(r#"{"timestamp":"2024-06-07T09:06:20.873354Z","level":"INFO","fields":{"message":"Finished stage","pipeline_stages":"1/12","stage":"Headers","checkpoint":20038569,"target":"None","stage_progress":"100.00%"},"target":"reth_node_events::node"}"#).unwrap();
+        processor.process_line(r#"{"timestamp":"2024-06-07T09:06:21.873354Z","level":"DEBUG","fields":{"message":"Validated state root","elapsed":"2.5s"},"target":"engine::tree"}"#).unwrap();
+
+        let pipeline = processor.current_pipeline.unwrap();
+        assert_eq!(pipeline.durations["001 - Headers"], Duration::from_secs(60));
+        assert_eq!(pipeline.stats["state_root"].mean, 2.5);
+    }
 }
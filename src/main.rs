@@ -1,25 +1,89 @@
 use eyre::Result;
-use log_parser::runner::Runner;
-use std::{env, io::stdout};
+use log_parser::{format::InputFormat, runner::Runner, time::TimestampSource};
+use std::{env, io::stdout, time::Duration};
+
+/// How often the summary is printed again in follow mode.
+const FOLLOW_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
 
 fn main() -> Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <path_to_log_file>", args[0]);
-        return Ok(());
+    let mut args = env::args();
+    let program = args.next().unwrap_or_default();
+
+    let mut log_file = None;
+    let mut follow = false;
+    let mut parallel = false;
+    let mut format = InputFormat::default();
+    let mut timestamp_priority = None;
+    let mut state_file = None;
+    let mut export_file = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" | "--follow" => follow = true,
+            "-p" | "--parallel" => parallel = true,
+            "--format" => match args.next() {
+                Some(name) => format = InputFormat::from_name(&name)?,
+                None => return usage(&program),
+            },
+            "--timestamp-source" => match args.next() {
+                Some(names) => {
+                    timestamp_priority = Some(
+                        names
+                            .split(',')
+                            .map(TimestampSource::from_name)
+                            .collect::<Result<Vec<_>>>()?,
+                    )
+                }
+                None => return usage(&program),
+            },
+            "--state-file" => match args.next() {
+                Some(path) => state_file = Some(path),
+                None => return usage(&program),
+            },
+            "--export" => match args.next() {
+                Some(path) => export_file = Some(path),
+                None => return usage(&program),
+            },
+            _ if log_file.is_none() => log_file = Some(arg),
+            _ => return usage(&program),
+        }
     }
+    let Some(log_file) = log_file else {
+        return usage(&program);
+    };
 
-    let log_file = &args[1];
     let stdout_writer = stdout();
 
-    let mut runner = Runner::builder()
-        .with_log_file(log_file)
+    let mut builder = Runner::builder()
+        .with_log_file(&log_file)
         .with_stdout_writer(stdout_writer)
-        .build()?;
+        .with_format(format)
+        .with_parallel(parallel);
+    if let Some(priority) = timestamp_priority {
+        builder = builder.with_timestamp_priority(priority);
+    }
+    if let Some(state_file) = state_file {
+        builder = builder.with_state_file(state_file);
+    }
+    if let Some(export_file) = export_file {
+        builder = builder.with_export_file(export_file);
+    }
+    if follow {
+        builder = builder.with_follow(FOLLOW_REFRESH_INTERVAL);
+    }
+    let mut runner = builder.build()?;
 
     runner.run()?;
 
     Ok(())
 }
+
+fn usage(program: &str) -> Result<()> {
+    eprintln!(
+        "Usage: {program} [--follow] [--parallel] [--format auto|text|journald-json|journald-export|docker|cri] \
+         [--timestamp-source reth,collector,syslog] [--state-file <path>] [--export <path>] \
+         <log file, logs directory, glob or - for stdin>"
+    );
+    Ok(())
+}
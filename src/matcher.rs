@@ -0,0 +1,479 @@
+use crate::issues::Level;
+use aho_corasick::AhoCorasick;
+use eyre::Result;
+use regex::{Captures, Regex};
+
+/// Kinds of lines events are extracted from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LineKind {
+    StageStart,
+    StageEnd,
+    /// The end of a run of a stage which hasn't reached its target yet.
+    StageCommit,
+    StateRoot,
+    /// Periodic health of the node, with the progress of the running stage
+    /// while the pipeline syncs.
+    Status,
+    UnwindStart,
+    UnwindEnd,
+    /// A block added to the canonical chain during live sync.
+    BlockAdded,
+    /// Engine API calls from the consensus layer, with the status reth
+    /// answered `forkchoiceUpdated` with.
+    ForkchoiceUpdated,
+    ForkchoiceSyncing,
+    ForkchoiceInvalid,
+    NewPayload,
+    NewPayloadInvalid,
+    /// Abnormal terminations of reth: a panic, being killed by the kernel or
+    /// systemd for running out of memory, failing to allocate, systemd
+    /// reporting its main process exited, and reth exiting with an error.
+    Panic,
+    OutOfMemory,
+    OomKiller,
+    AllocationFailed,
+    ProcessExited,
+    ErrorExit,
+    /// The startup banner of reth.
+    Startup,
+}
+
+impl LineKind {
+    const ALL: [LineKind; 20] = [
+        LineKind::StageStart,
+        LineKind::StageEnd,
+        LineKind::StageCommit,
+        LineKind::StateRoot,
+        LineKind::Status,
+        LineKind::UnwindStart,
+        LineKind::UnwindEnd,
+        LineKind::BlockAdded,
+        LineKind::ForkchoiceUpdated,
+        LineKind::ForkchoiceSyncing,
+        LineKind::ForkchoiceInvalid,
+        LineKind::NewPayload,
+        LineKind::NewPayloadInvalid,
+        LineKind::Panic,
+        LineKind::OutOfMemory,
+        LineKind::OomKiller,
+        LineKind::AllocationFailed,
+        LineKind::ProcessExited,
+        LineKind::ErrorExit,
+        LineKind::Startup,
+    ];
+
+    /// Literal contained by every line of this kind, in text and JSON logs.
+    pub(crate) fn literal(self) -> &'static str {
+        match self {
+            LineKind::StageStart => "Preparing stage",
+            LineKind::StageEnd => "Finished stage",
+            LineKind::StageCommit => "Committed stage progress",
+            LineKind::StateRoot => "Validated state root",
+            LineKind::Status => "connected_peers",
+            LineKind::UnwindStart => "Unwinding",
+            LineKind::UnwindEnd => "Unwound stage",
+            LineKind::BlockAdded => "Block added to canonical chain",
+            LineKind::ForkchoiceUpdated => "Forkchoice updated",
+            LineKind::ForkchoiceSyncing => "Received forkchoice updated message when syncing",
+            LineKind::ForkchoiceInvalid => "Received invalid forkchoice updated message",
+            LineKind::NewPayload => "Received block from consensus engine",
+            LineKind::NewPayloadInvalid => "Invalid block error on new payload",
+            LineKind::Panic => "panicked at",
+            LineKind::OutOfMemory => "Out of memory: Killed process",
+            LineKind::OomKiller => "killed by the OOM killer",
+            LineKind::AllocationFailed => "memory allocation of",
+            LineKind::ProcessExited => "Main process exited, code=",
+            LineKind::ErrorExit => "Error: ",
+            LineKind::Startup => "Starting reth",
+        }
+    }
+
+    /// Whether the literal of this kind must start the message, or follow the
+    /// panicking thread for panics.
+    fn starts_message(self) -> bool {
+        matches!(self, LineKind::ErrorExit | LineKind::Panic)
+    }
+
+    /// Pattern matched by text lines of this kind from their literal on,
+    /// capturing their fields.
+    fn pattern(self) -> &'static str {
+        match self {
+            LineKind::StageStart => {
+                r"^Preparing stage pipeline_stages=(?<index>\d+)/(?<count>\d+) stage=(?<stage>\w+) checkpoint=(?<checkpoint>\d+) target=(?<target>\w+)"
+            }
+            LineKind::StageEnd => {
+                r"^Finished stage pipeline_stages=\d+/\d+ stage=(?<stage>\w+) checkpoint=(?<checkpoint>\d+) target=(?<target>\w+)(?: stage_progress=(?<progress>\d+\.\d+)%)?(?: stage_eta=(?<eta>.+))?"
+            }
+            LineKind::StageCommit => {
+                r"^Committed stage progress pipeline_stages=\d+/\d+ stage=(?<stage>\w+) checkpoint=(?<checkpoint>\d+) target=(?<target>\w+)(?: stage_progress=(?<progress>\d+\.\d+)%)?(?: stage_eta=(?<eta>.+))?"
+            }
+            LineKind::StateRoot => {
+                r"^Validated state root(?:.*? (?:number|block_number|block)=#?(?<block>\d+))?.*elapsed=(?<elapsed>\d+(?:\.\d+)?(?:ns|µs|us|ms|s))"
+            }
+            LineKind::Status => {
+                r"^connected_peers=(?<peers>\d+)(?: freelist=(?<freelist>\d+))?(?: latest_block=(?<latest_block>\d+))?(?: stage=(?<stage>\w+) checkpoint=(?<checkpoint>\d+) target=(?<target>\w+) stage_progress=(?<progress>\d+\.\d+)%(?: stage_eta=(?<eta>.+))?)?"
+            }
+            LineKind::UnwindStart => {
+                r"^Unwinding(?: pipeline_stages=\d+/\d+)? stage=(?<stage>\w+) checkpoint=(?<checkpoint>\d+) target=(?<target>\w+)"
+            }
+            LineKind::UnwindEnd => {
+                r"^Unwound stage(?: pipeline_stages=\d+/\d+)? stage=(?<stage>\w+) checkpoint=(?<checkpoint>\d+)"
+            }
+            LineKind::BlockAdded => {
+                r"^Block added to canonical chain number=(?<number>\d+) hash=(?<hash>\w+)(?:.*? txs=(?<txs>\d+))?(?:.*? (?:gas|gas_used)=(?<gas>\d+(?:\.\d+)? ?[KMG]?gas))?(?:.*? mgas=(?<mgas>\d+(?:\.\d+)?))?.* elapsed=(?<elapsed>\d+(?:\.\d+)?(?:ns|µs|us|ms|s))"
+            }
+            LineKind::ForkchoiceUpdated => {
+                r"^Forkchoice updated(?:.*? head_block_hash=(?<hash>\w+))?(?:.* elapsed=(?<elapsed>\d+(?:\.\d+)?(?:ns|µs|us|ms|s)))?"
+            }
+            LineKind::ForkchoiceSyncing => {
+                r"^Received forkchoice updated message when syncing(?:.*? head_block_hash=(?<hash>\w+))?(?:.* elapsed=(?<elapsed>\d+(?:\.\d+)?(?:ns|µs|us|ms|s)))?"
+            }
+            LineKind::ForkchoiceInvalid => {
+                r"^Received invalid forkchoice updated message(?:.*? head_block_hash=(?<hash>\w+))?(?:.* elapsed=(?<elapsed>\d+(?:\.\d+)?(?:ns|µs|us|ms|s)))?"
+            }
+            LineKind::NewPayload => {
+                r"^Received block from consensus engine(?:.*? number=(?<number>\d+))?(?:.*? hash=(?<hash>\w+))?"
+            }
+            LineKind::NewPayloadInvalid => {
+                r"^Invalid block error on new payload(?:.*? invalid_hash=(?<hash>\w+))?(?:.*? invalid_number=(?:Some\()?(?<number>\d+))?"
+            }
+            LineKind::Panic => r"^panicked at ",
+            LineKind::OutOfMemory => r"^Out of memory: Killed process \d+ \((?<process>[^)]*)\)",
+            LineKind::OomKiller => r"^killed by the OOM killer",
+            LineKind::AllocationFailed => r"^memory allocation of \d+ bytes failed",
+            LineKind::ProcessExited => {
+                r"^Main process exited, code=(?<code>\w+), status=(?<status>\d+)(?:/(?<signal>\w+))?"
+            }
+            LineKind::ErrorExit => r"^Error: ",
+            LineKind::Startup => r"^Starting reth\b",
+        }
+    }
+}
+
+/// Pattern matched by text lines logged at a level reported as an issue from
+/// their level on, capturing their `target`, when logged, and `message`.
+const ISSUE_PATTERN: &str = r"^(?:ERROR|WARN) +(?:(?<target>\w+(?:::\w+)*): )?(?<message>.*)";
+
+/// Syslog prefix of text lines, e.g.
+/// `Jun 04 20:44:41 reth-5 reth-mainnet[4009735]: `, capturing the PID of the
+/// process when it tells.
+pub(crate) const SYSLOG_PREFIX: &str =
+    r"^[A-Z][a-z]{2} +\d{1,2} \d{2}:\d{2}:\d{2} \S+ [^\s\[:]+(?:\[(\d+)\])?: ";
+
+/// Finds out which kind of line a log line is, running only the patterns
+/// whose literal the line contains.
+#[derive(Clone)]
+pub(crate) struct LineMatcher {
+    literals: AhoCorasick,
+    patterns: Vec<Regex>,
+    issue: Regex,
+    syslog_prefix: Regex,
+}
+
+impl LineMatcher {
+    pub(crate) fn new() -> Result<Self> {
+        Ok(LineMatcher {
+            literals: AhoCorasick::new(
+                LineKind::ALL
+                    .map(LineKind::literal)
+                    .into_iter()
+                    .chain(Level::ALL.map(Level::literal)),
+            )?,
+            patterns: LineKind::ALL
+                .iter()
+                .map(|kind| Regex::new(kind.pattern()))
+                .collect::<Result<_, _>>()?,
+            issue: Regex::new(ISSUE_PATTERN)?,
+            syslog_prefix: Regex::new(SYSLOG_PREFIX)?,
+        })
+    }
+
+    /// Whether the line contains the literal of any kind or level.
+    pub(crate) fn is_candidate(&self, line: &str) -> bool {
+        self.literals.is_match(line)
+    }
+
+    /// Kind of a text line, along with the fields captured by its pattern.
+    pub(crate) fn captures<'l>(&self, line: &'l str) -> Option<(LineKind, Captures<'l>)> {
+        self.literals.find_iter(line).find_map(|literal| {
+            let index = literal.pattern().as_usize();
+            let kind = *LineKind::ALL.get(index)?;
+            let mut prefix = &line[..literal.start()];
+            if kind == LineKind::Panic {
+                prefix = before_panicking_thread(prefix)?;
+            }
+            if kind.starts_message() && !self.starts_message(prefix) {
+                return None;
+            }
+            let captures = self.patterns[index].captures(&line[literal.start()..])?;
+            Some((kind, captures))
+        })
+    }
+
+    /// Whether `prefix` is nothing or the prefix of a process, like syslog's.
+    fn starts_message(&self, prefix: &str) -> bool {
+        prefix.is_empty()
+            || prefix.ends_with("]: ")
+            || self
+                .syslog_prefix
+                .find(prefix)
+                .is_some_and(|found| found.end() == prefix.len())
+    }
+
+    /// Level reported as an issue whose literal the line contains.
+    pub(crate) fn issue_level(&self, line: &str) -> Option<Level> {
+        self.literals.find_iter(line).find_map(|literal| {
+            let index = literal.pattern().as_usize();
+            index
+                .checked_sub(LineKind::ALL.len())
+                .map(|index| Level::ALL[index])
+        })
+    }
+
+    /// Level of a text line logged at a level reported as an issue, along
+    /// with the fields captured by [`ISSUE_PATTERN`].
+    pub(crate) fn issue<'l>(&self, line: &'l str) -> Option<(Level, Captures<'l>)> {
+        self.literals.find_iter(line).find_map(|literal| {
+            let index = literal
+                .pattern()
+                .as_usize()
+                .checked_sub(LineKind::ALL.len())?;
+            let follows_space =
+                literal.start() == 0 || line[..literal.start()].ends_with(char::is_whitespace);
+            if !follows_space {
+                return None;
+            }
+            let captures = self.issue.captures(&line[literal.start()..])?;
+            Some((Level::ALL[index], captures))
+        })
+    }
+}
+
+/// What comes before the `thread '<name>' ` a panic starts with, if it does.
+fn before_panicking_thread(prefix: &str) -> Option<&str> {
+    let start = prefix.strip_suffix("' ")?.rfind("thread '")?;
+    Some(&prefix[..start])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_matcher() {
+        let matcher = LineMatcher::new().unwrap();
+
+        let line = "2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=20037711 target=None";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::StageStart);
+        assert_eq!(
+            (
+                &captures["stage"],
+                &captures["checkpoint"],
+                &captures["target"]
+            ),
+            ("Headers", "20037711", "None")
+        );
+        assert_eq!((&captures["index"], &captures["count"]), ("1", "12"));
+
+        let line = "2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=20038569 target=None stage_progress=100.00%";
+        assert_eq!(matcher.captures(line).unwrap().0, LineKind::StageEnd);
+
+        let line =
+            "2024-06-07T09:06:20.873354Z DEBUG Validated state root number=20038569 elapsed=12.5ms";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::StateRoot);
+        assert_eq!(
+            (&captures["block"], &captures["elapsed"]),
+            ("20038569", "12.5ms")
+        );
+
+        let line = "2024-06-07T09:06:20.873354Z DEBUG Validated state root elapsed=2s";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::StateRoot);
+        assert!(captures.name("block").is_none());
+        assert_eq!(&captures["elapsed"], "2s");
+
+        let line = "2024-06-07T09:06:20.873354Z  INFO Committed stage progress pipeline_stages=2/12 stage=Bodies checkpoint=3000 target=20110161 stage_progress=0.01% stage_eta=1h 2m 3s";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::StageCommit);
+        assert_eq!(
+            (
+                &captures["stage"],
+                &captures["checkpoint"],
+                &captures["progress"],
+                &captures["eta"]
+            ),
+            ("Bodies", "3000", "0.01", "1h 2m 3s")
+        );
+
+        let line = "2024-06-07T09:06:20.873354Z  INFO Status connected_peers=10 freelist=4 stage=Bodies checkpoint=1000 target=20110161 stage_progress=0.00%";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::Status);
+        assert_eq!(
+            (
+                &captures["peers"],
+                &captures["freelist"],
+                &captures["stage"],
+                &captures["progress"]
+            ),
+            ("10", "4", "Bodies", "0.00")
+        );
+        assert!(captures.name("latest_block").is_none());
+        assert!(captures.name("eta").is_none());
+
+        let line = "2024-06-07T09:06:20.873354Z  INFO Unwinding pipeline_stages=12/12 stage=Finish checkpoint=20038569 target=20038500";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::UnwindStart);
+        assert_eq!(
+            (
+                &captures["stage"],
+                &captures["checkpoint"],
+                &captures["target"]
+            ),
+            ("Finish", "20038569", "20038500")
+        );
+
+        let line =
+            "2024-06-07T09:06:20.873354Z  INFO Unwound stage stage=Finish checkpoint=20038500";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::UnwindEnd);
+        assert_eq!(
+            (&captures["stage"], &captures["checkpoint"]),
+            ("Finish", "20038500")
+        );
+
+        let line = "2024-06-19T09:21:22.123456Z  INFO Block added to canonical chain number=20124789 hash=0xf0a3 peers=88 txs=150 gas=12.34 Mgas gas_throughput=401.12 Mgas/second full=41.1% base_fee=3.45gwei blobs=0 excess_blobs=0 elapsed=30.712ms";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::BlockAdded);
+        assert_eq!(
+            (
+                &captures["number"],
+                &captures["hash"],
+                &captures["txs"],
+                &captures["gas"],
+                &captures["elapsed"]
+            ),
+            ("20124789", "0xf0a3", "150", "12.34 Mgas", "30.712ms")
+        );
+
+        let line = "2024-06-19T09:21:22.123456Z  INFO Block added to canonical chain number=20124789 hash=0xf0a3 peers=88 txs=150 mgas=12.340 full=41.1% base_fee=3.45gwei elapsed=1.2s";
+        let (_, captures) = matcher.captures(line).unwrap();
+        assert_eq!(
+            (&captures["mgas"], &captures["elapsed"]),
+            ("12.340", "1.2s")
+        );
+        assert!(captures.name("gas").is_none());
+
+        let line = "2024-06-19T09:21:22.523456Z  INFO Forkchoice updated head_block_hash=0xf0a3 safe_block_hash=0x11aa finalized_block_hash=0x22bb";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::ForkchoiceUpdated);
+        assert_eq!(&captures["hash"], "0xf0a3");
+        assert!(captures.name("elapsed").is_none());
+
+        let line = "2024-06-19T09:21:22.523456Z  INFO Received forkchoice updated message when syncing head_block_hash=0xf0a3 safe_block_hash=0x11aa finalized_block_hash=0x22bb elapsed=1.5ms";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::ForkchoiceSyncing);
+        assert_eq!(&captures["elapsed"], "1.5ms");
+
+        let line = "2024-06-19T09:21:22.023456Z  INFO Received block from consensus engine number=20124789 hash=0xf0a3";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::NewPayload);
+        assert_eq!(
+            (&captures["number"], &captures["hash"]),
+            ("20124789", "0xf0a3")
+        );
+
+        let line = "2024-06-19T09:21:22.023456Z  WARN Invalid block error on new payload invalid_hash=0xf0a3 invalid_number=Some(20124789) validation_err=state root mismatch";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::NewPayloadInvalid);
+        assert_eq!(
+            (&captures["number"], &captures["hash"]),
+            ("20124789", "0xf0a3")
+        );
+
+        let line = "thread 'tokio-runtime-worker' panicked at crates/stages/src/lib.rs:10:5:";
+        assert_eq!(matcher.captures(line).unwrap().0, LineKind::Panic);
+        let line = "Jun 07 09:06:20 reth-5 reth[4009735]: thread 'main' panicked at 'called `Result::unwrap()` on an `Err` value', bin/reth/src/main.rs:35:9";
+        assert_eq!(matcher.captures(line).unwrap().0, LineKind::Panic);
+        let line =
+            "Jun 04 20:44:41 reth-5 reth: thread 'main' panicked at bin/reth/src/main.rs:35:9:";
+        assert_eq!(matcher.captures(line).unwrap().0, LineKind::Panic);
+        let line = "2024-06-07T09:06:20.873354Z  WARN reth_tasks: Critical task panicked at crates/net/network/src/manager.rs:10:5 name=\"p2p txpool\"";
+        assert!(matcher.captures(line).is_none());
+        let line = "2024-06-07T09:06:20.873354Z ERROR thread 'tokio-runtime-worker' panicked at crates/stages/src/lib.rs:10:5:";
+        assert!(matcher.captures(line).is_none());
+
+        let line = "Jun 07 09:06:20 reth-5 kernel: Out of memory: Killed process 4009735 (reth) total-vm:70392576kB, anon-rss:62810112kB";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::OutOfMemory);
+        assert_eq!(&captures["process"], "reth");
+
+        let line = "Jun 07 09:06:20 reth-5 systemd[1]: reth.service: Main process exited, code=killed, status=9/KILL";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::ProcessExited);
+        assert_eq!(
+            (&captures["code"], &captures["status"], &captures["signal"]),
+            ("killed", "9", "KILL")
+        );
+
+        let line =
+            "Jun 07 09:06:20 reth-5 reth-mainnet[4009735]: Error: failed to open the database";
+        assert_eq!(matcher.captures(line).unwrap().0, LineKind::ErrorExit);
+        let line = "Jun 04 20:44:41 reth-5 reth: Error: failed to open the database";
+        assert_eq!(matcher.captures(line).unwrap().0, LineKind::ErrorExit);
+        let line = "2024-06-07T09:06:20.873354Z  WARN Session error err=Error: timeout";
+        assert!(matcher.captures(line).is_none());
+
+        let line = "2024-06-07T09:00:00.000000Z  INFO Starting reth version=\"1.0.0 (1fc9b1f)\"";
+        assert_eq!(matcher.captures(line).unwrap().0, LineKind::Startup);
+
+        // The literal alone isn't enough for text lines.
+        let line = r#"{"fields":{"message":"Preparing stage","stage":"Headers"}}"#;
+        assert!(matcher.is_candidate(line));
+        assert!(matcher.captures(line).is_none());
+
+        let line =
+            "2024-06-07T09:06:20.873354Z  INFO Status connected_peers=12 latest_block=20038569";
+        let (kind, captures) = matcher.captures(line).unwrap();
+        assert_eq!(kind, LineKind::Status);
+        assert_eq!(
+            (&captures["peers"], &captures["latest_block"]),
+            ("12", "20038569")
+        );
+        assert!(captures.name("stage").is_none());
+
+        let line = "2024-06-07T09:06:20.873354Z  INFO Transaction pool maintained";
+        assert!(!matcher.is_candidate(line));
+    }
+
+    #[test]
+    fn test_issue_lines() {
+        let matcher = LineMatcher::new().unwrap();
+
+        let line = "2024-06-07T09:06:20.873354Z  WARN net::session: Session error peer_id=0x01 err=timeout";
+        assert!(matcher.is_candidate(line));
+        let (level, captures) = matcher.issue(line).unwrap();
+        assert_eq!(level, Level::Warn);
+        assert_eq!(
+            (&captures["target"], &captures["message"]),
+            ("net::session", "Session error peer_id=0x01 err=timeout")
+        );
+
+        let line = "2024-06-07T09:06:20.873354Z ERROR Database error code=12";
+        let (level, captures) = matcher.issue(line).unwrap();
+        assert_eq!(level, Level::Error);
+        assert!(captures.name("target").is_none());
+        assert_eq!(&captures["message"], "Database error code=12");
+
+        // Levels are only read where the level is written.
+        let line = "2024-06-07T09:06:20.873354Z  INFO Received message kind=ERROR_REPORT";
+        assert!(matcher.issue(line).is_none());
+        let line = "2024-06-07T09:06:20.873354Z  INFO Received a WARNING";
+        assert!(matcher.issue(line).is_none());
+
+        let line =
+            r#"{"level":"WARN","fields":{"message":"Session error"},"target":"net::session"}"#;
+        assert_eq!(matcher.issue_level(line), Some(Level::Warn));
+    }
+}
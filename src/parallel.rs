@@ -0,0 +1,338 @@
+use crate::{
+    format::{EntryReader, InputFormat, LogEntry},
+    input::is_compressed,
+    line_parser::{LineParser, ParsedLine},
+    log_processor::LogProcessor,
+};
+use eyre::Result;
+use log::{error, info};
+use memmap2::Mmap;
+use std::{fs::File, num::NonZeroUsize, path::Path, str, thread, time::SystemTime};
+
+/// What a parallel parse went through.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct ParseStats {
+    pub(crate) lines: usize,
+    pub(crate) bytes: u64,
+    pub(crate) ansi_cleaned_lines: usize,
+}
+
+/// Entries of a chunk that may produce events, in the order of the file,
+/// along with what was parsed from them.
+struct Chunk {
+    entries: Vec<(LogEntry, ParsedLine)>,
+    lines: usize,
+    ansi_cleaned_lines: usize,
+    /// Last timestamp parsed from the chunk.
+    timestamp_reference: Option<SystemTime>,
+}
+
+/// Parses a log file in one chunk per available CPU, or returns `None`
+/// without processing anything when it can't be split.
+pub(crate) fn process_file(
+    path: &Path,
+    format: InputFormat,
+    processor: &mut LogProcessor,
+) -> Result<Option<ParseStats>> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() {
+        return Ok(None);
+    }
+    if metadata.len() == 0 {
+        return Ok(Some(ParseStats::default()));
+    }
+
+    // SAFETY: the file must not be truncated while it's mapped, which reth
+    // doesn't do to its logs: they are rotated by renaming them.
+    let data = unsafe { Mmap::map(&file)? };
+    let chunks = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    Ok(process_data(&data, format, processor, chunks))
+}
+
+fn process_data(
+    data: &[u8],
+    format: InputFormat,
+    processor: &mut LogProcessor,
+    chunks: usize,
+) -> Option<ParseStats> {
+    if is_compressed(data) {
+        return None;
+    }
+    let format = match format {
+        InputFormat::Auto => {
+            let format = detect(data);
+            info!("Reading {format:?} input");
+            format
+        }
+        format => format,
+    };
+    if !format.is_line_oriented() {
+        return None;
+    }
+
+    let parser = processor.parser();
+    let chunks = split(data, chunks);
+    // Syslog years are inferred from the last full date before each chunk.
+    let mut references = vec![parser.timestamp_reference()];
+    for previous in &chunks[..chunks.len().saturating_sub(1)] {
+        let last = references.last().copied().flatten();
+        let reference = last_full_date(previous, parser).or(last)?;
+        references.push(Some(reference));
+    }
+    let chunks = thread::scope(|scope| {
+        chunks
+            .into_iter()
+            .zip(references)
+            .map(|(chunk, reference)| {
+                let mut parser = parser.clone();
+                parser.set_timestamp_reference(reference);
+                scope.spawn(move || parse_chunk(chunk, format, parser))
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().expect("chunk parser panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    let mut stats = ParseStats {
+        bytes: data.len() as u64,
+        ..Default::default()
+    };
+    for chunk in chunks {
+        stats.lines += chunk.lines;
+        stats.ansi_cleaned_lines += chunk.ansi_cleaned_lines;
+        for (entry, parsed) in chunk.entries {
+            processor
+                .apply(&entry.message, &entry.metadata, parsed)
+                .unwrap_or_else(|err| {
+                    error!("Error processing line {}: {err}", entry.message);
+                });
+        }
+        if let Some(reference) = chunk.timestamp_reference {
+            processor.set_timestamp_reference(reference);
+        }
+    }
+    Some(stats)
+}
+
+/// Detects the format from the first non-blank line, like [`EntryReader`].
+fn detect(data: &[u8]) -> InputFormat {
+    data.split(|byte| *byte == b'\n')
+        .filter_map(|line| str::from_utf8(line.strip_suffix(b"\r").unwrap_or(line)).ok())
+        .find(|line| !line.trim().is_empty())
+        .map_or(InputFormat::Text, InputFormat::detect)
+}
+
+/// Last timestamp with a full date in `data`, found from its end.
+fn last_full_date(data: &[u8], parser: &LineParser) -> Option<SystemTime> {
+    data.rsplit(|byte| *byte == b'\n')
+        .filter_map(|line| str::from_utf8(line).ok())
+        .find_map(|line| parser.reth_timestamp(line))
+}
+
+/// Splits `data` in at most `count` chunks of about the same size, each
+/// ending at a line boundary.
+fn split(data: &[u8], count: usize) -> Vec<&[u8]> {
+    let mut chunks = Vec::with_capacity(count);
+    let mut rest = data;
+    for remaining in (1..=count).rev() {
+        if rest.is_empty() {
+            break;
+        }
+        let target = rest.len() / remaining;
+        let end = match rest[target..].iter().position(|byte| *byte == b'\n') {
+            Some(newline) => target + newline + 1,
+            None => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+fn parse_chunk(data: &[u8], format: InputFormat, mut parser: LineParser) -> Chunk {
+    let mut reader = EntryReader::new(format);
+    let mut entries = Vec::new();
+    let mut lines = 0;
+    // Report lines don't look relevant, and may continue the previous chunk.
+    let mut in_report = true;
+    for line in data.split_inclusive(|byte| *byte == b'\n') {
+        lines += 1;
+        let line = match line.strip_suffix(b"\n") {
+            Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+            None => line,
+        };
+        let Ok(line) = str::from_utf8(line) else {
+            continue;
+        };
+        if let Some(entry) = reader.entry_from_line(line.to_string()) {
+            let relevant = parser.is_relevant(&entry.message);
+            if relevant || in_report {
+                let parsed = parser.parse(&entry.message, &entry.metadata, in_report);
+                in_report = relevant || parsed.continuation.is_some();
+                entries.push((entry, parsed));
+            }
+        }
+    }
+
+    Chunk {
+        entries,
+        lines,
+        ansi_cleaned_lines: reader.ansi_cleaned_lines(),
+        timestamp_reference: parser.timestamp_reference(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn summary(mut processor: LogProcessor) -> String {
+        processor.finish();
+        let mut output = Cursor::new(Vec::new());
+        processor.print_summary(&mut output);
+        processor.print_issues(&mut output);
+        String::from_utf8(output.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_split() {
+        let data = b"first\nsecond\nthird\nfourth";
+        assert_eq!(split(data, 1), vec![&data[..]]);
+        assert_eq!(
+            split(data, 2),
+            vec![&b"first\nsecond\n"[..], &b"third\nfourth"[..]]
+        );
+        assert_eq!(split(data, 10).concat(), data);
+        assert!(split(b"", 4).is_empty());
+    }
+
+    #[test]
+    fn test_process_data_matches_sequential() {
+        for data in [
+            &include_bytes!("../tests/data/input-print-summary-multiple-pipelines.txt")[..],
+            &include_bytes!("../tests/data/input-print-summary-reth-json.txt")[..],
+        ] {
+            let mut sequential = LogProcessor::new().unwrap();
+            for line in data.split(|byte| *byte == b'\n') {
+                sequential
+                    .process_line(str::from_utf8(line).unwrap())
+                    .unwrap();
+            }
+            let expected = summary(sequential);
+
+            for chunks in [1, 3, 16] {
+                let mut processor = LogProcessor::new().unwrap();
+                let stats = process_data(data, InputFormat::Auto, &mut processor, chunks).unwrap();
+                assert_eq!(
+                    stats.lines,
+                    data.split_inclusive(|byte| *byte == b'\n').count()
+                );
+                assert_eq!(summary(processor), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_process_data_keeps_reports() {
+        let mut log = String::from("2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=0 target=None\n");
+        log.push_str("thread 'tokio-runtime-worker' panicked at crates/stages/src/lib.rs:10:5:\n");
+        log.push_str("called `Option::unwrap()` on a `None` value\n");
+        log.push_str("stack backtrace:\n");
+        for frame in 0..20 {
+            log.push_str(&format!("  {frame:>2}: reth::frame_{frame}\n"));
+        }
+        log.push_str("2024-06-07T09:05:30.000000Z  INFO Transaction pool maintained\n");
+        log.push_str("Error: not part of the panic\n");
+        let data = log.as_bytes();
+
+        let mut sequential = LogProcessor::new().unwrap();
+        for line in log.lines() {
+            sequential.process_line(line).unwrap();
+        }
+        assert_eq!(
+            sequential
+                .current_pipeline
+                .as_ref()
+                .unwrap()
+                .abort
+                .as_ref()
+                .unwrap()
+                .backtrace
+                .len(),
+            21
+        );
+        let expected = summary(sequential);
+
+        for chunks in [1, 3, 16] {
+            let mut processor = LogProcessor::new().unwrap();
+            process_data(data, InputFormat::Text, &mut processor, chunks).unwrap();
+            assert_eq!(summary(processor), expected);
+        }
+    }
+
+    #[test]
+    fn test_process_data_infers_syslog_years() {
+        let mut log = String::new();
+        for index in 0..40 {
+            let (day, hour) = if index < 20 {
+                ("Dec 31", 23)
+            } else {
+                ("Jan  1", 0)
+            };
+            let minute = index % 20 * 3;
+            let checkpoint = index * 100;
+            log.push_str(&format!("{day} {hour:02}:{minute:02}:00 reth-5 reth[100]: INFO Preparing stage pipeline_stages=1/1 stage=Finish checkpoint={checkpoint} target=None\n"));
+            log.push_str(&format!("{day} {hour:02}:{minute:02}:30 reth-5 reth[100]: INFO Finished stage pipeline_stages=1/1 stage=Finish checkpoint={} target=None\n", checkpoint + 100));
+        }
+        let without_full_date = log.clone();
+        log.insert_str(
+            0,
+            "2024-12-31T22:59:00.000000Z  INFO Starting reth version=\"1.0.0\"\n",
+        );
+
+        let mut sequential = LogProcessor::new().unwrap();
+        for line in log.lines() {
+            sequential.process_line(line).unwrap();
+        }
+        let expected = summary(sequential);
+
+        for chunks in [1, 3, 16] {
+            let mut processor = LogProcessor::new().unwrap();
+            process_data(log.as_bytes(), InputFormat::Text, &mut processor, chunks).unwrap();
+            assert_eq!(summary(processor), expected);
+        }
+
+        let mut processor = LogProcessor::new().unwrap();
+        assert_eq!(
+            process_data(
+                without_full_date.as_bytes(),
+                InputFormat::Text,
+                &mut processor,
+                3
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_process_data_unsupported() {
+        let mut processor = LogProcessor::new().unwrap();
+        assert_eq!(
+            process_data(
+                b"{\"log\":\"line\\n\"}\n",
+                InputFormat::Auto,
+                &mut processor,
+                2
+            ),
+            None
+        );
+        assert_eq!(
+            process_data(&[0x1f, 0x8b, 0x08], InputFormat::Text, &mut processor, 2),
+            None
+        );
+    }
+}
@@ -1,16 +1,262 @@
-use crate::{stats, time::format_duration};
+use crate::{
+    abort::Abort,
+    event::Progress,
+    health::{HealthSummary, StatusSample},
+    state_root::{StateRootSummary, StateRootValidation},
+    stats,
+    time::format_duration,
+};
 use eyre::Result;
 use log::debug;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     time::{Duration, SystemTime},
 };
 
-#[derive(Default, Clone)]
+/// Block numbers logged when a stage starts or finishes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageCheckpoint {
+    /// Block the stage has processed up to.
+    pub checkpoint: Option<u64>,
+    /// Block the stage runs up to, `None` when it hasn't been determined yet,
+    /// as for the `Headers` stage.
+    pub target: Option<u64>,
+}
+
+impl StageCheckpoint {
+    /// Parses the `checkpoint=` and `target=` values of a stage line.
+    pub(crate) fn parse(checkpoint: &str, target: &str) -> Self {
+        StageCheckpoint {
+            checkpoint: checkpoint.parse().ok(),
+            target: target.parse().ok(),
+        }
+    }
+}
+
+/// Position of a stage in its pipeline, logged as `pipeline_stages=N/M`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineStages {
+    /// Position of the stage, starting from 1.
+    pub index: usize,
+    /// Number of stages the pipeline declared.
+    pub count: usize,
+}
+
+impl PipelineStages {
+    /// Parses the `N/M` value of `pipeline_stages=`.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        let (index, count) = value.split_once('/')?;
+        Some(PipelineStages {
+            index: index.parse().ok()?,
+            count: count.parse().ok()?,
+        })
+    }
+
+    /// Whether a stage at this position starts a new pipeline after one at
+    /// `previous`: the index went back, or the number of stages changed.
+    pub(crate) fn starts_pipeline_after(&self, previous: &PipelineStages) -> bool {
+        self.index < previous.index || self.count != previous.count
+    }
+}
+
+/// Blocks processed by a stage run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockRange {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub target: Option<u64>,
+}
+
+impl BlockRange {
+    /// Describes the range and the throughput of a stage which ran for
+    /// `duration`, e.g. `blocks 0-20020834, 2187.6 blocks/s`.
+    fn describe(&self, duration: &Duration) -> Option<String> {
+        let (from, to) = (self.from?, self.to?);
+        let mut description = format!("blocks {from}-{to}");
+        let blocks = to.saturating_sub(from);
+        // Durations are shown in whole seconds, stages which took less than
+        // one, like the unwind stages skipping to their target, have no
+        // meaningful throughput.
+        if blocks > 0 && duration.as_secs() > 0 {
+            let throughput = blocks as f64 / duration.as_secs_f64();
+            description.push_str(&format!(", {throughput:.1} blocks/s"));
+        }
+        if let Some(target) = self.target.filter(|target| *target > to) {
+            description.push_str(&format!(", target {target}"));
+        }
+        Some(description)
+    }
+}
+
+/// Progress of a stage, as reported while it ran.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ProgressSample {
+    pub timestamp: SystemTime,
+    pub checkpoint: Option<u64>,
+    /// Percentage of the blocks up to the target the stage went through.
+    pub progress: f64,
+    /// How much longer reth estimated the stage would run, `None` when it
+    /// couldn't tell.
+    pub eta: Option<Duration>,
+}
+
+impl ProgressSample {
+    pub(crate) fn new(
+        timestamp: SystemTime,
+        checkpoint: StageCheckpoint,
+        progress: Progress,
+    ) -> Self {
+        ProgressSample {
+            timestamp,
+            checkpoint: checkpoint.checkpoint,
+            progress: progress.percent,
+            eta: progress.eta,
+        }
+    }
+}
+
+/// One execution of a stage, from `Preparing stage` to the `Committed stage
+/// progress` or `Finished stage` line after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageRun {
+    pub start: SystemTime,
+    /// `None` while the run is still going.
+    pub end: Option<SystemTime>,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub target: Option<u64>,
+    /// Whether the run ended by committing rather than starting again.
+    pub committed: bool,
+}
+
+/// A stage being unwound, from `Unwinding` to `Unwound stage`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageUnwind {
+    pub stage: String,
+    pub start: SystemTime,
+    /// `None` while the stage is still being unwound.
+    pub end: Option<SystemTime>,
+    /// Checkpoint of the stage before the unwind.
+    pub from: Option<u64>,
+    /// Checkpoint the stage was unwound to.
+    pub to: Option<u64>,
+}
+
+/// Stages unwound one after the other to the same block, after a reorg, a
+/// bad block or a `reth stage unwind`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnwindPhase {
+    /// Block the stages are unwound to.
+    pub target: Option<u64>,
+    pub stages: Vec<StageUnwind>,
+}
+
+impl UnwindPhase {
+    /// Highest checkpoint the stages were unwound from.
+    pub fn from(&self) -> Option<u64> {
+        self.stages.iter().filter_map(|unwind| unwind.from).max()
+    }
+
+    /// Time between the first stage starting to unwind and the last one
+    /// being unwound.
+    pub fn duration(&self) -> Duration {
+        let start = self.stages.iter().map(|unwind| unwind.start).min();
+        let end = self.stages.iter().filter_map(|unwind| unwind.end).max();
+        match (start, end) {
+            (Some(start), Some(end)) => end.duration_since(start).unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// Why the stages of a pipeline that were still running stopped being logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interruption {
+    /// reth restarted, and started another pipeline.
+    Restart,
+    /// reth started another pipeline without restarting.
+    NewPipeline,
+    /// reth panicked.
+    Panic,
+    /// reth was killed for running out of memory, or failed to allocate.
+    OutOfMemory,
+    /// reth was killed by a signal or dumped core.
+    Crash,
+    /// reth exited with an error.
+    ErrorExit,
+    /// The log ends, while reth may still be running them.
+    EndOfLog,
+}
+
+impl Interruption {
+    fn describe(self) -> &'static str {
+        match self {
+            Interruption::Restart => "interrupted by a restart",
+            Interruption::NewPipeline => "interrupted by a new pipeline",
+            Interruption::Panic => "interrupted by a panic",
+            Interruption::OutOfMemory => "interrupted by running out of memory",
+            Interruption::Crash => "interrupted by a crash",
+            Interruption::ErrorExit => "interrupted by an error",
+            Interruption::EndOfLog => "in progress at the end of the log",
+        }
+    }
+
+    /// Name of an abnormal termination of reth.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Interruption::Restart => "restart",
+            Interruption::NewPipeline => "new pipeline",
+            Interruption::Panic => "panic",
+            Interruption::OutOfMemory => "out of memory",
+            Interruption::Crash => "crash",
+            Interruption::ErrorExit => "exit with an error",
+            Interruption::EndOfLog => "end of the log",
+        }
+    }
+}
+
+/// How the ETAs reth logged while a stage ran compare with when it finished.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EtaAccuracy {
+    /// Number of ETAs logged.
+    pub estimates: usize,
+    /// Mean difference between the estimated and the actual finish time.
+    pub mean_error: Duration,
+    /// Mean of the errors relative to the time the stage actually had left.
+    pub mean_relative_error: f64,
+    /// Number of ETAs which were earlier than the actual finish time.
+    pub optimistic: usize,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Pipeline {
     pub stages: HashMap<String, (SystemTime, Option<SystemTime>)>,
     pub durations: HashMap<String, Duration>,
+    /// Every run of each stage, keyed by stage name.
+    pub runs: HashMap<String, Vec<StageRun>>,
+    /// Blocks processed by finished stages, keyed like `durations`.
+    pub blocks: HashMap<String, BlockRange>,
+    /// Progress reported by stages while they ran, keyed by stage name.
+    pub progress: HashMap<String, Vec<ProgressSample>>,
     pub stats: HashMap<String, stats::Stats>,
+    /// Position of the last stage started, which declares how many stages the
+    /// pipeline has.
+    pub pipeline_stages: Option<PipelineStages>,
+    /// Index of the session the pipeline ran in.
+    pub session: usize,
+    /// When and why the stages still running stopped being logged.
+    pub interruption: Option<(SystemTime, Interruption)>,
+    /// Unwinds that happened during the pipeline, in order.
+    pub unwinds: Vec<UnwindPhase>,
+    /// Health of the node reported by `Status` lines during the pipeline.
+    pub status: Vec<StatusSample>,
+    /// State root validations, in the order they were logged.
+    pub state_roots: Vec<StateRootValidation>,
+    /// How reth terminated abnormally during the pipeline, if it did.
+    pub abort: Option<Abort>,
+    /// Host and process the pipeline ran on, when known from the log collector.
+    pub origin: Option<String>,
 }
 
 impl Pipeline {
@@ -22,27 +268,292 @@ impl Pipeline {
         format!("{:03} - {}", self.stages.len(), stage_name)
     }
 
-    pub fn record_stage_start(&mut self, stage_name: &str, timestamp: SystemTime) {
-        if !self.stages.contains_key(stage_name) {
-            self.stages
-                .insert(stage_name.to_string(), (timestamp, None));
+    /// When the first stage of the pipeline started.
+    pub fn start_time(&self) -> Option<SystemTime> {
+        self.stages
+            .values()
+            .map(|(start_time, _)| *start_time)
+            .min()
+    }
+
+    /// Number of stages the pipeline declared, and how many of them finished.
+    pub fn finished_stages(&self) -> (Option<usize>, usize) {
+        let finished = self
+            .stages
+            .values()
+            .filter(|(_, end_time)| end_time.is_some())
+            .count();
+        (self.pipeline_stages.map(|stages| stages.count), finished)
+    }
+
+    /// Records that the stages still running stopped being logged at
+    /// `timestamp`, the last one seen, unless an earlier interruption was
+    /// recorded already.
+    pub fn interrupt(&mut self, timestamp: SystemTime, reason: Interruption) {
+        if self.interruption.is_none() && !self.unfinished_stages().is_empty() {
+            self.interruption = Some((timestamp, reason));
         }
     }
 
-    pub fn record_stage_end(&mut self, stage_name: &str, timestamp: SystemTime) -> Result<()> {
-        if let Some((start_time, _)) = self.stages.get_mut(stage_name) {
-            let duration = timestamp.duration_since(*start_time)?;
-            let name = self.enumerated_stage_name(stage_name);
-            debug!("inserting duration for {name}");
-            self.durations.insert(name.clone(), duration);
-            self.stats
-                .entry(name)
-                .or_default()
-                .update(duration.as_secs_f64());
+    /// Whether the stage timed under `key`, e.g. `001 - Headers`, finished.
+    fn is_finished(&self, key: &str) -> bool {
+        let stage_name = key.split_once(" - ").map_or(key, |(_, name)| name);
+        !matches!(self.stages.get(stage_name), Some((_, None)))
+    }
+
+    /// Time spent in the stages which finished, the sum of their runs.
+    pub fn finished_duration(&self) -> Duration {
+        self.durations
+            .iter()
+            .filter(|(key, _)| self.is_finished(key))
+            .map(|(_, duration)| *duration)
+            .sum()
+    }
+
+    /// Stages which started but didn't finish, in the order they started,
+    /// along with their position in the pipeline.
+    fn unfinished_stages(&self) -> Vec<(usize, &String, SystemTime)> {
+        let mut stages: Vec<(&String, &(SystemTime, Option<SystemTime>))> =
+            self.stages.iter().collect();
+        stages.sort_by_key(|(_, (start_time, _))| *start_time);
+        stages
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (_, (_, end_time)))| end_time.is_none())
+            .map(|(index, (stage_name, (start_time, _)))| (index + 1, stage_name, *start_time))
+            .collect()
+    }
+
+    /// The stage which started last among those still running, if any.
+    pub fn running_stage(&self) -> Option<&str> {
+        self.unfinished_stages()
+            .last()
+            .map(|&(_, stage_name, _)| stage_name.as_str())
+    }
+
+    /// Records reth terminating abnormally, returning whether it was recorded.
+    pub fn record_abort(&mut self, abort: Abort, last_timestamp: Option<SystemTime>) -> bool {
+        match &self.abort {
+            None => {
+                if let Some(timestamp) = abort.timestamp.or(last_timestamp) {
+                    self.interrupt(timestamp, abort.reason);
+                }
+            }
+            Some(previous)
+                if previous.reason == Interruption::Crash
+                    && abort.reason == Interruption::OutOfMemory =>
+            {
+                if let Some((_, reason)) = &mut self.interruption {
+                    *reason = abort.reason;
+                }
+            }
+            Some(_) => return false,
+        }
+        self.abort = Some(abort);
+        true
+    }
+
+    /// Whether a stage starting at `position` belongs to a new pipeline.
+    pub(crate) fn is_followed_by(
+        &self,
+        stage_name: &str,
+        position: Option<PipelineStages>,
+    ) -> bool {
+        let restarts = matches!(self.stages.get(stage_name), Some((_, Some(_))));
+        restarts
+            || position
+                .zip(self.pipeline_stages)
+                .is_some_and(|(position, previous)| position.starts_pipeline_after(&previous))
+    }
+
+    /// Records a run of a stage starting, ending the previous one if needed.
+    pub fn record_stage_start(
+        &mut self,
+        stage_name: &str,
+        timestamp: SystemTime,
+        checkpoint: StageCheckpoint,
+    ) {
+        self.stages
+            .entry(stage_name.to_string())
+            .or_insert((timestamp, None));
+
+        let name = self.enumerated_stage_name(stage_name);
+        let runs = self.runs.entry(stage_name.to_string()).or_default();
+        let interrupted = runs.last_mut().filter(|run| run.end.is_none()).map(|run| {
+            debug!("{stage_name} started again before committing");
+            run.end = Some(timestamp);
+            timestamp.duration_since(run.start).unwrap_or_default()
+        });
+        runs.push(StageRun {
+            start: timestamp,
+            end: None,
+            from: checkpoint.checkpoint,
+            to: None,
+            target: checkpoint.target,
+            committed: false,
+        });
+        if let Some(duration) = interrupted {
+            self.add_run_duration(name, duration);
+        }
+    }
+
+    /// Records the running run of a stage committing its progress, and the
+    /// stage finishing if `done`.
+    pub fn record_stage_end(
+        &mut self,
+        stage_name: &str,
+        timestamp: SystemTime,
+        checkpoint: StageCheckpoint,
+        done: bool,
+    ) -> Result<()> {
+        let name = self.enumerated_stage_name(stage_name);
+        let Some(runs) = self.runs.get_mut(stage_name) else {
+            return Ok(());
+        };
+        let from = runs.first().and_then(|run| run.from);
+        if let Some(run) = runs.last_mut().filter(|run| run.end.is_none()) {
+            let duration = timestamp.duration_since(run.start)?;
+            run.end = Some(timestamp);
+            run.to = checkpoint.checkpoint;
+            run.target = checkpoint.target.or(run.target);
+            run.committed = true;
+
+            self.blocks.insert(
+                name.clone(),
+                BlockRange {
+                    from,
+                    to: run.to,
+                    target: run.target,
+                },
+            );
+            self.add_run_duration(name, duration);
+        }
+
+        if done {
+            if let Some((_, end_time)) = self.stages.get_mut(stage_name) {
+                *end_time = Some(timestamp);
+            }
         }
         Ok(())
     }
 
+    /// Adds the duration of an ended run to the time of the stage timed under
+    /// `name`.
+    fn add_run_duration(&mut self, name: String, duration: Duration) {
+        debug!(
+            "adding {} to the duration of {name}",
+            format_duration(&duration)
+        );
+        *self.durations.entry(name.clone()).or_default() += duration;
+        self.stats
+            .entry(name)
+            .or_default()
+            .update(duration.as_secs_f64());
+    }
+
+    /// Records a stage starting to unwind from `checkpoint.checkpoint` to
+    /// `checkpoint.target`.
+    pub fn record_unwind_start(
+        &mut self,
+        stage_name: &str,
+        timestamp: SystemTime,
+        checkpoint: StageCheckpoint,
+    ) {
+        let same_phase = self.unwinds.last().is_some_and(|phase| {
+            phase.target == checkpoint.target
+                && !phase.stages.iter().any(|unwind| unwind.stage == stage_name)
+        });
+        if !same_phase {
+            self.unwinds.push(UnwindPhase {
+                target: checkpoint.target,
+                stages: Vec::new(),
+            });
+        }
+
+        if let Some(phase) = self.unwinds.last_mut() {
+            phase.stages.push(StageUnwind {
+                stage: stage_name.to_string(),
+                start: timestamp,
+                end: None,
+                from: checkpoint.checkpoint,
+                to: checkpoint.target,
+            });
+        }
+    }
+
+    /// Records a stage being unwound, if it was unwinding.
+    pub fn record_unwind_end(
+        &mut self,
+        stage_name: &str,
+        timestamp: SystemTime,
+        checkpoint: StageCheckpoint,
+    ) {
+        let unwind = self.unwinds.last_mut().and_then(|phase| {
+            phase
+                .stages
+                .iter_mut()
+                .rev()
+                .find(|unwind| unwind.stage == stage_name && unwind.end.is_none())
+        });
+        if let Some(unwind) = unwind {
+            unwind.end = Some(timestamp);
+            unwind.to = checkpoint.checkpoint.or(unwind.to);
+        }
+    }
+
+    /// Adds to the progress of a stage, if it's running.
+    pub fn record_stage_progress(&mut self, stage_name: &str, sample: ProgressSample) {
+        if let Some((_, None)) = self.stages.get(stage_name) {
+            self.progress
+                .entry(stage_name.to_string())
+                .or_default()
+                .push(sample);
+        }
+    }
+
+    /// Compares the ETAs logged while a finished stage ran with when it
+    /// finished, if any was logged.
+    pub fn eta_accuracy(&self, stage_name: &str) -> Option<EtaAccuracy> {
+        let (_, Some(end_time)) = self.stages.get(stage_name)? else {
+            return None;
+        };
+
+        let mut estimates = 0;
+        let mut total_error = Duration::ZERO;
+        let mut total_relative_error = 0.0;
+        let mut optimistic = 0;
+        for sample in self.progress.get(stage_name)? {
+            let (Some(eta), Ok(remaining)) =
+                (sample.eta, end_time.duration_since(sample.timestamp))
+            else {
+                continue;
+            };
+            let error = if eta < remaining {
+                optimistic += 1;
+                remaining - eta
+            } else {
+                eta - remaining
+            };
+            estimates += 1;
+            total_error += error;
+            if !remaining.is_zero() {
+                total_relative_error += error.as_secs_f64() / remaining.as_secs_f64();
+            }
+        }
+
+        (estimates > 0).then(|| EtaAccuracy {
+            estimates,
+            mean_error: total_error / estimates as u32,
+            mean_relative_error: total_relative_error / estimates as f64,
+            optimistic,
+        })
+    }
+
+    pub fn record_state_root(&mut self, validation: StateRootValidation) {
+        self.update_stats("state_root", validation.elapsed);
+        self.state_roots.push(validation);
+    }
+
     pub fn update_stats(&mut self, label: &str, elapsed: f64) {
         self.stats
             .entry(label.to_string())
@@ -51,24 +562,208 @@ impl Pipeline {
     }
 
     pub fn print_summary<W: std::io::Write>(&self, index: usize, writer: &mut W) {
-        writeln!(writer, "Pipeline {}:", index + 1).unwrap();
+        self.print_header(index, writer);
+        self.print_stages(writer);
+        if let Some((timestamp, reason)) = self.interruption {
+            self.print_unfinished_stages(writer, timestamp, reason.describe());
+        }
+        if let Some(abort) = &self.abort {
+            abort.print(writer);
+        }
+        self.print_unwinds(writer);
+        self.print_eta_accuracy(writer);
+        self.print_state_roots(writer);
+        self.print_health(writer);
+        self.print_total(writer);
+    }
 
+    /// Like [`Pipeline::print_summary`], also listing the stages that haven't
+    /// finished yet with the time elapsed between their start and `now`.
+    pub fn print_live_summary<W: std::io::Write>(
+        &self,
+        index: usize,
+        writer: &mut W,
+        now: SystemTime,
+    ) {
+        self.print_header(index, writer);
+        self.print_stages(writer);
+        self.print_unfinished_stages(writer, now, "in progress");
+        if let Some(abort) = &self.abort {
+            abort.print(writer);
+        }
+        self.print_unwinds(writer);
+        self.print_state_roots(writer);
+        self.print_health(writer);
+        self.print_total(writer);
+    }
+
+    fn print_header<W: std::io::Write>(&self, index: usize, writer: &mut W) {
+        match &self.origin {
+            Some(origin) => writeln!(writer, "Pipeline {} ({}):", index + 1, origin).unwrap(),
+            None => writeln!(writer, "Pipeline {}:", index + 1).unwrap(),
+        }
+    }
+
+    fn print_stages<W: std::io::Write>(&self, writer: &mut W) {
         let mut keys: Vec<&String> = self.durations.keys().collect();
         keys.sort();
 
         for key in keys {
+            // Stages which didn't finish are listed with the time they ran for.
+            if !self.is_finished(key) {
+                continue;
+            }
+
+            let duration = &self.durations[key];
+            match self
+                .blocks
+                .get(key)
+                .and_then(|blocks| blocks.describe(duration))
+            {
+                Some(blocks) => writeln!(
+                    writer,
+                    "  Stage {}: {} ({})",
+                    key,
+                    format_duration(duration),
+                    blocks
+                ),
+                None => writeln!(writer, "  Stage {}: {}", key, format_duration(duration)),
+            }
+            .unwrap();
+
+            if let Some(stats) = self.stats.get(key).filter(|stats| stats.count > 1) {
+                let seconds = |value: f64| format_duration(&Duration::from_secs_f64(value));
+                writeln!(
+                    writer,
+                    "    {} runs: mean {}, std {}, min {}, max {}",
+                    stats.count,
+                    seconds(stats.mean),
+                    seconds(stats.std_dev()),
+                    seconds(stats.min),
+                    seconds(stats.max)
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    /// Lists the stages which didn't finish, with the time elapsed between
+    /// their start and `until`, their status and the latest progress they
+    /// reported.
+    fn print_unfinished_stages<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        until: SystemTime,
+        status: &str,
+    ) {
+        for (position, stage_name, start_time) in self.unfinished_stages() {
+            let elapsed = until.duration_since(start_time).unwrap_or_default();
+            let mut status = status.to_string();
+            if let Some(sample) = self
+                .progress
+                .get(stage_name)
+                .and_then(|samples| samples.last())
+            {
+                status.push_str(&format!(", {:.2}%", sample.progress));
+                if let Some(eta) = &sample.eta {
+                    status.push_str(&format!(", ETA {}", format_duration(eta)));
+                }
+            }
+            writeln!(
+                writer,
+                "  Stage {:03} - {}: {} ({})",
+                position,
+                stage_name,
+                format_duration(&elapsed),
+                status
+            )
+            .unwrap();
+        }
+    }
+
+    /// Lists the unwind phases, with the stages unwound in each of them.
+    fn print_unwinds<W: std::io::Write>(&self, writer: &mut W) {
+        let block = |block: Option<u64>| block.map_or("?".to_string(), |block| block.to_string());
+
+        for (index, phase) in self.unwinds.iter().enumerate() {
             writeln!(
                 writer,
-                "  Stage {}: {}",
-                key,
-                format_duration(self.durations.get(key).unwrap())
+                "  Unwind {}: blocks {}-{}, {}",
+                index + 1,
+                block(phase.from()),
+                block(phase.target),
+                format_duration(&phase.duration())
             )
             .unwrap();
+            for unwind in &phase.stages {
+                let duration = match unwind.end {
+                    Some(end) => {
+                        format_duration(&end.duration_since(unwind.start).unwrap_or_default())
+                    }
+                    None => "in progress".to_string(),
+                };
+                writeln!(
+                    writer,
+                    "    Stage {}: {} (blocks {}-{})",
+                    unwind.stage,
+                    duration,
+                    block(unwind.from),
+                    block(unwind.to)
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    /// Lists how accurate the ETAs of the finished stages were, in the order
+    /// the stages ran.
+    fn print_eta_accuracy<W: std::io::Write>(&self, writer: &mut W) {
+        let mut stages: Vec<(&String, &SystemTime)> = self
+            .stages
+            .iter()
+            .map(|(stage_name, (start_time, _))| (stage_name, start_time))
+            .collect();
+        stages.sort_by_key(|(_, start_time)| *start_time);
+
+        let mut printed_header = false;
+        for (stage_name, _) in stages {
+            let Some(accuracy) = self.eta_accuracy(stage_name) else {
+                continue;
+            };
+            if !printed_header {
+                writeln!(writer, "  ETA accuracy:").unwrap();
+                printed_header = true;
+            }
+            writeln!(
+                writer,
+                "    {}: {} estimates off by {} on average ({:.1}% of the time left), {} optimistic",
+                stage_name,
+                accuracy.estimates,
+                format_duration(&accuracy.mean_error),
+                accuracy.mean_relative_error * 100.0,
+                accuracy.optimistic
+            )
+            .unwrap();
+        }
+    }
+
+    fn print_state_roots<W: std::io::Write>(&self, writer: &mut W) {
+        if let Some(summary) = StateRootSummary::new(&self.state_roots) {
+            summary.print("State Root", "  ", writer);
+        }
+    }
+
+    fn print_health<W: std::io::Write>(&self, writer: &mut W) {
+        if let Some(health) = HealthSummary::new(&self.status) {
+            writeln!(writer, "  Node Health: {}", health.describe()).unwrap();
         }
+    }
+
+    fn print_total<W: std::io::Write>(&self, writer: &mut W) {
         writeln!(
             writer,
             "  Total Pipeline Duration: {}",
-            format_duration(&self.durations.values().cloned().sum())
+            format_duration(&self.finished_duration())
         )
         .unwrap();
     }
@@ -85,7 +780,7 @@ mod tests {
         let stage_name = "Headers";
         let timestamp = SystemTime::now();
 
-        pipeline.record_stage_start(stage_name, timestamp);
+        pipeline.record_stage_start(stage_name, timestamp, StageCheckpoint::default());
 
         assert_eq!(pipeline.stages.len(), 1);
         assert!(pipeline.stages.contains_key(stage_name));
@@ -100,8 +795,8 @@ mod tests {
         let start_time = SystemTime::now();
         let end_time = start_time + Duration::from_secs(60); // 1 minute later
 
-        pipeline.record_stage_start(stage_name, start_time);
-        pipeline.record_stage_end(stage_name, end_time)?;
+        pipeline.record_stage_start(stage_name, start_time, StageCheckpoint::default());
+        pipeline.record_stage_end(stage_name, end_time, StageCheckpoint::default(), true)?;
 
         assert_eq!(pipeline.durations.len(), 1);
         assert!(pipeline.durations.contains_key(name));
@@ -129,8 +824,10 @@ mod tests {
         let start_time = SystemTime::now();
         let end_time = start_time + Duration::from_secs(60); // 1 minute later
 
-        pipeline.record_stage_start(stage_name, start_time);
-        pipeline.record_stage_end(stage_name, end_time).unwrap();
+        pipeline.record_stage_start(stage_name, start_time, StageCheckpoint::default());
+        pipeline
+            .record_stage_end(stage_name, end_time, StageCheckpoint::default(), true)
+            .unwrap();
 
         let mut output = Vec::new();
         pipeline.print_summary(0, &mut output);
@@ -142,6 +839,104 @@ mod tests {
         assert_eq!(expected_output, output_str);
     }
 
+    #[test]
+    fn test_print_summary_blocks() {
+        let mut pipeline = Pipeline::new();
+        let start_time = SystemTime::now();
+
+        pipeline.record_stage_start(
+            "Bodies",
+            start_time,
+            StageCheckpoint::parse("0", "20020834"),
+        );
+        pipeline
+            .record_stage_end(
+                "Bodies",
+                start_time + Duration::from_secs(9150),
+                StageCheckpoint::parse("20020834", "20020834"),
+                true,
+            )
+            .unwrap();
+        pipeline.record_stage_start(
+            "Execution",
+            start_time + Duration::from_secs(9150),
+            StageCheckpoint::parse("0", "20020834"),
+        );
+        pipeline
+            .record_stage_end(
+                "Execution",
+                start_time + Duration::from_secs(9160),
+                StageCheckpoint::parse("1000", "None"),
+                true,
+            )
+            .unwrap();
+
+        assert_eq!(
+            pipeline.blocks["001 - Bodies"],
+            BlockRange {
+                from: Some(0),
+                to: Some(20020834),
+                target: Some(20020834),
+            }
+        );
+
+        let mut output = Vec::new();
+        pipeline.print_summary(0, &mut output);
+
+        let output_str = String::from_utf8(output).unwrap();
+        let expected_output = "Pipeline 1:\n  Stage 001 - Bodies: 2h 32m (blocks 0-20020834, 2188.1 blocks/s)\n  Stage 002 - Execution: 10s (blocks 0-1000, 100.0 blocks/s, target 20020834)\n  Total Pipeline Duration: 2h 32m\n"
+            .to_string();
+        assert_eq!(expected_output, output_str);
+    }
+
+    #[test]
+    fn test_eta_accuracy() {
+        let mut pipeline = Pipeline::new();
+        let start_time = SystemTime::now();
+        let sample = |minutes: u64, eta: Option<u64>| ProgressSample {
+            timestamp: start_time + Duration::from_secs(minutes * 60),
+            checkpoint: None,
+            progress: minutes as f64 * 10.0,
+            eta: eta.map(|eta| Duration::from_secs(eta * 60)),
+        };
+
+        pipeline.record_stage_progress("Bodies", sample(0, Some(5)));
+        pipeline.record_stage_start("Bodies", start_time, StageCheckpoint::default());
+        pipeline.record_stage_progress("Bodies", sample(2, Some(6)));
+        pipeline.record_stage_progress("Bodies", sample(4, None));
+        pipeline.record_stage_progress("Bodies", sample(6, Some(6)));
+        assert_eq!(pipeline.progress["Bodies"].len(), 3);
+        assert_eq!(pipeline.eta_accuracy("Bodies"), None);
+
+        pipeline
+            .record_stage_end(
+                "Bodies",
+                start_time + Duration::from_secs(600),
+                StageCheckpoint::default(),
+                true,
+            )
+            .unwrap();
+        pipeline.record_stage_progress("Bodies", sample(12, Some(1)));
+        assert_eq!(pipeline.progress["Bodies"].len(), 3);
+
+        // Estimated finishes at 8m and 12m, for an actual one at 10m.
+        assert_eq!(
+            pipeline.eta_accuracy("Bodies"),
+            Some(EtaAccuracy {
+                estimates: 2,
+                mean_error: Duration::from_secs(120),
+                mean_relative_error: (2.0 / 8.0 + 2.0 / 4.0) / 2.0,
+                optimistic: 1,
+            })
+        );
+
+        let mut output = Vec::new();
+        pipeline.print_summary(0, &mut output);
+        let output_str = String::from_utf8(output).unwrap();
+        let expected_output = "Pipeline 1:\n  Stage 001 - Bodies: 10m 0s\n  ETA accuracy:\n    Bodies: 2 estimates off by 2m 0s on average (37.5% of the time left), 1 optimistic\n  Total Pipeline Duration: 10m 0s\n";
+        assert_eq!(expected_output, output_str);
+    }
+
     #[test]
     fn test_print_summary_long_stage() {
         let mut pipeline = Pipeline::new();
@@ -149,8 +944,10 @@ mod tests {
         let start_time = SystemTime::now();
         let end_time = start_time + Duration::from_secs(157326);
 
-        pipeline.record_stage_start(stage_name, start_time);
-        pipeline.record_stage_end(stage_name, end_time).unwrap();
+        pipeline.record_stage_start(stage_name, start_time, StageCheckpoint::default());
+        pipeline
+            .record_stage_end(stage_name, end_time, StageCheckpoint::default(), true)
+            .unwrap();
 
         let mut output = Vec::new();
         pipeline.print_summary(0, &mut output);
@@ -162,23 +959,203 @@ mod tests {
         assert_eq!(expected_output, output_str);
     }
 
+    #[test]
+    fn test_print_live_summary() {
+        let mut pipeline = Pipeline::new();
+        let start_time = SystemTime::now();
+
+        pipeline.record_stage_start("Headers", start_time, StageCheckpoint::default());
+        pipeline
+            .record_stage_end(
+                "Headers",
+                start_time + Duration::from_secs(60),
+                StageCheckpoint::default(),
+                true,
+            )
+            .unwrap();
+        pipeline.record_stage_start(
+            "Bodies",
+            start_time + Duration::from_secs(60),
+            StageCheckpoint::default(),
+        );
+
+        let mut output = Vec::new();
+        pipeline.print_live_summary(0, &mut output, start_time + Duration::from_secs(150));
+
+        let output_str = String::from_utf8(output).unwrap();
+        let expected_output = "Pipeline 1:\n  Stage 001 - Headers: 1m 0s\n  Stage 002 - Bodies: 1m 30s (in progress)\n  Total Pipeline Duration: 1m 0s\n"
+            .to_string();
+        assert_eq!(expected_output, output_str);
+    }
+
     #[test]
     fn test_multiple_record_stage_start() {
         let mut pipeline = Pipeline::new();
         let stage_name = "Headers";
         let first_timestamp = SystemTime::now();
 
-        pipeline.record_stage_start(stage_name, first_timestamp);
+        pipeline.record_stage_start(stage_name, first_timestamp, StageCheckpoint::default());
 
         assert_eq!(pipeline.stages.len(), 1);
         assert!(pipeline.stages.contains_key(stage_name));
         assert_eq!(pipeline.stages[stage_name], (first_timestamp, None));
 
         let second_timestamp = SystemTime::now();
-        pipeline.record_stage_start(stage_name, second_timestamp);
+        pipeline.record_stage_start(stage_name, second_timestamp, StageCheckpoint::default());
 
         assert_eq!(pipeline.stages.len(), 1);
         assert!(pipeline.stages.contains_key(stage_name));
         assert_eq!(pipeline.stages[stage_name], (first_timestamp, None));
+        assert_eq!(pipeline.runs[stage_name].len(), 2);
+        assert_eq!(pipeline.runs[stage_name][0].end, Some(second_timestamp));
+        assert!(!pipeline.runs[stage_name][0].committed);
+        assert_eq!(pipeline.runs[stage_name][1].start, second_timestamp);
+    }
+
+    #[test]
+    fn test_record_stage_start_again_before_committing() -> Result<()> {
+        let mut pipeline = Pipeline::new();
+        let start_time = SystemTime::now();
+        pipeline.record_stage_start("Execution", start_time, StageCheckpoint::parse("0", "300"));
+        pipeline.record_stage_start(
+            "Execution",
+            start_time + Duration::from_secs(60),
+            StageCheckpoint::parse("0", "300"),
+        );
+        pipeline.record_stage_end(
+            "Execution",
+            start_time + Duration::from_secs(240),
+            StageCheckpoint::parse("300", "300"),
+            true,
+        )?;
+
+        assert_eq!(
+            pipeline.runs["Execution"],
+            vec![
+                StageRun {
+                    start: start_time,
+                    end: Some(start_time + Duration::from_secs(60)),
+                    from: Some(0),
+                    to: None,
+                    target: Some(300),
+                    committed: false,
+                },
+                StageRun {
+                    start: start_time + Duration::from_secs(60),
+                    end: Some(start_time + Duration::from_secs(240)),
+                    from: Some(0),
+                    to: Some(300),
+                    target: Some(300),
+                    committed: true,
+                },
+            ]
+        );
+        assert_eq!(pipeline.finished_duration(), Duration::from_secs(240));
+
+        let mut output = Vec::new();
+        pipeline.print_summary(0, &mut output);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Pipeline 1:\n  \
+             Stage 001 - Execution: 4m 0s (blocks 0-300, 1.2 blocks/s)\n    \
+             2 runs: mean 2m 0s, std 1m 0s, min 1m 0s, max 3m 0s\n  \
+             Total Pipeline Duration: 4m 0s\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_stage_runs() -> Result<()> {
+        let mut pipeline = Pipeline::new();
+        let start_time = SystemTime::now();
+        pipeline.record_stage_start("Execution", start_time, StageCheckpoint::parse("0", "300"));
+        pipeline.record_stage_end(
+            "Execution",
+            start_time + Duration::from_secs(100),
+            StageCheckpoint::parse("100", "300"),
+            false,
+        )?;
+        assert_eq!(pipeline.stages["Execution"], (start_time, None));
+
+        pipeline.record_stage_start(
+            "Execution",
+            start_time + Duration::from_secs(101),
+            StageCheckpoint::parse("100", "300"),
+        );
+        pipeline.record_stage_end(
+            "Execution",
+            start_time + Duration::from_secs(401),
+            StageCheckpoint::parse("300", "300"),
+            true,
+        )?;
+
+        assert_eq!(
+            pipeline.runs["Execution"],
+            vec![
+                StageRun {
+                    start: start_time,
+                    end: Some(start_time + Duration::from_secs(100)),
+                    from: Some(0),
+                    to: Some(100),
+                    target: Some(300),
+                    committed: true,
+                },
+                StageRun {
+                    start: start_time + Duration::from_secs(101),
+                    end: Some(start_time + Duration::from_secs(401)),
+                    from: Some(100),
+                    to: Some(300),
+                    target: Some(300),
+                    committed: true,
+                },
+            ]
+        );
+        assert_eq!(
+            pipeline.stages["Execution"],
+            (start_time, Some(start_time + Duration::from_secs(401)))
+        );
+
+        let mut output = Vec::new();
+        pipeline.print_summary(0, &mut output);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Pipeline 1:\n  \
+             Stage 001 - Execution: 6m 40s (blocks 0-300, 0.8 blocks/s)\n    \
+             2 runs: mean 3m 20s, std 1m 40s, min 1m 40s, max 5m 0s\n  \
+             Total Pipeline Duration: 6m 40s\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_unwinds() {
+        let mut pipeline = Pipeline::new();
+        let start_time = SystemTime::now();
+        let at = |secs| start_time + Duration::from_secs(secs);
+
+        pipeline.record_unwind_start("Finish", at(0), StageCheckpoint::parse("1000", "900"));
+        pipeline.record_unwind_end("Finish", at(1), StageCheckpoint::parse("900", ""));
+        pipeline.record_unwind_start("Execution", at(1), StageCheckpoint::parse("1000", "900"));
+        pipeline.record_unwind_end("Execution", at(31), StageCheckpoint::parse("900", ""));
+        // Unwinding a stage again starts another phase.
+        pipeline.record_unwind_start("Finish", at(40), StageCheckpoint::parse("900", "900"));
+
+        assert_eq!(pipeline.unwinds.len(), 2);
+        assert_eq!(pipeline.unwinds[0].stages.len(), 2);
+        assert_eq!(pipeline.unwinds[0].from(), Some(1000));
+        assert_eq!(pipeline.unwinds[0].duration(), Duration::from_secs(31));
+
+        let mut output = Vec::new();
+        pipeline.print_summary(0, &mut output);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Pipeline 1:\n  \
+             Unwind 1: blocks 1000-900, 31s\n    \
+             Stage Finish: 1s (blocks 1000-900)\n    \
+             Stage Execution: 30s (blocks 1000-900)\n  \
+             Unwind 2: blocks 900-900, 0s\n    \
+             Stage Finish: in progress (blocks 900-900)\n  \
+             Total Pipeline Duration: 0s\n"
+        );
     }
 }
@@ -1,16 +1,33 @@
-use crate::log_processor::LogProcessor;
+use crate::{
+    export,
+    format::{EntryReader, InputFormat},
+    input::{Input, LogSource},
+    log_processor::LogProcessor,
+    parallel,
+    state::SavedState,
+    time::TimestampSource,
+};
 use eyre::Result;
 use log::{error, info};
 use std::{
-    fs::File,
-    io::{BufRead, BufReader, Write},
-    path::Path,
-    time::Instant,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 
+/// How long to wait for new lines once a followed log has been read to its end.
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 pub struct Runner<W: Write> {
-    log_file: String,
+    source: Option<LogSource>,
     stdout_writer: W,
+    format: InputFormat,
+    timestamp_priority: Option<Vec<TimestampSource>>,
+    follow: Option<Duration>,
+    state_file: Option<PathBuf>,
+    export_file: Option<PathBuf>,
+    parallel: bool,
 }
 
 impl<W: Write> Runner<W> {
@@ -19,71 +36,226 @@ impl<W: Write> Runner<W> {
     }
 
     pub fn run(&mut self) -> Result<()> {
-        let path = Path::new(&self.log_file);
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-
-        let total_lines = {
-            let file = File::open(path)?;
-            let reader = BufReader::new(file);
-            reader.lines().count()
-        };
+        let source = self
+            .source
+            .take()
+            .ok_or_else(|| eyre::eyre!("log source was already consumed"))?;
 
         let mut processor = LogProcessor::new()?;
+        if let Some(priority) = &self.timestamp_priority {
+            processor.set_timestamp_priority(priority.clone());
+        }
 
-        let start_time = Instant::now();
-        for (index, line) in reader.lines().enumerate() {
-            if let Ok(line) = line {
-                processor.process_line(&line).unwrap_or_else(|err| {
-                    error!("Error processing line {line}: {err}");
-                });
-            }
-            let lines_processed = index + 1;
-
-            if lines_processed % 5000 == 0 || lines_processed == total_lines {
-                let percentage = (lines_processed as f64 / total_lines as f64) * 100.0;
-                let elapsed_time = start_time.elapsed().as_secs();
-                info!(
-                    "Processed {} lines out of {} ({:.2}%) in {} seconds",
-                    lines_processed, total_lines, percentage, elapsed_time
-                );
-            }
+        let ansi_cleaned_lines = match self.parse_parallel(&source, &mut processor)? {
+            Some(ansi_cleaned_lines) => ansi_cleaned_lines,
+            None => self.parse_sequential(source, &mut processor)?,
+        };
+        if ansi_cleaned_lines > 0 {
+            info!("Removed ANSI escape sequences from {ansi_cleaned_lines} lines");
         }
 
         // Capture the last pipeline if it was still in progress
-        let pipelines = &mut processor.pipelines;
-        let current_pipeline = &processor.current_pipeline;
-        if let Some(pipeline) = current_pipeline {
-            pipelines.push(pipeline.clone());
+        processor.finish();
+
+        if let Some(export_file) = &self.export_file {
+            export::write(export_file, &processor)?;
+            info!("Exported the summary to {}", export_file.display());
         }
 
         processor.print_summary(&mut self.stdout_writer);
+        processor.print_issues(&mut self.stdout_writer);
 
         Ok(())
     }
 
+    /// Parses the log file in parallel chunks, when enabled and possible for
+    /// it. Returns the number of lines ANSI escape sequences were removed from.
+    fn parse_parallel(
+        &self,
+        source: &LogSource,
+        processor: &mut LogProcessor,
+    ) -> Result<Option<usize>> {
+        if !self.parallel {
+            return Ok(None);
+        }
+        let LogSource::File(log_file) = source else {
+            info!("Only single log files can be parsed in parallel, parsing sequentially");
+            return Ok(None);
+        };
+
+        let start_time = Instant::now();
+        let Some(stats) = parallel::process_file(log_file, self.format, processor)? else {
+            info!(
+                "{} can't be split for parallel parsing, parsing sequentially",
+                log_file.display()
+            );
+            return Ok(None);
+        };
+        report_progress(stats.lines, stats.bytes, Some(stats.bytes), start_time);
+        Ok(Some(stats.ansi_cleaned_lines))
+    }
+
+    /// Parses the log line by line, following it and saving the parser state
+    /// if requested. Returns the number of lines ANSI escape sequences were
+    /// removed from.
+    fn parse_sequential(
+        &mut self,
+        source: LogSource,
+        processor: &mut LogProcessor,
+    ) -> Result<usize> {
+        let state_files = match (self.state_file.take(), &source) {
+            (Some(state_file), LogSource::File(log_file)) => Some((state_file, log_file.clone())),
+            (Some(_), _) => eyre::bail!("a state file can only be used with a single log file"),
+            (None, _) => None,
+        };
+        let saved_state = match &state_files {
+            Some((state_file, log_file)) => SavedState::load(state_file, log_file)?,
+            None => None,
+        };
+
+        let mut input = source.open_at(saved_state.as_ref().map_or(0, |state| state.offset))?;
+        if self.follow.is_some() {
+            input.follow()?;
+        }
+        if let Some(state) = saved_state {
+            info!("Resuming from byte {} of the log", state.offset);
+            processor.restore(state.processor);
+        }
+
+        let mut reader = EntryReader::new(self.format);
+        let start_time = Instant::now();
+        let mut lines_processed = 0;
+        let mut last_refresh: Option<Instant> = None;
+        loop {
+            let Some(entry) = reader.next_entry(&mut input)? else {
+                let Some(refresh_interval) = self.follow else {
+                    break;
+                };
+                if last_refresh.is_none_or(|refresh| refresh.elapsed() >= refresh_interval) {
+                    report_input_progress(lines_processed, &input, start_time);
+                    processor.print_live_summary(&mut self.stdout_writer, SystemTime::now());
+                    self.stdout_writer.flush()?;
+                    save_state(state_files.as_ref(), &input, processor)?;
+                    last_refresh = Some(Instant::now());
+                }
+                thread::sleep(FOLLOW_POLL_INTERVAL);
+                input.reopen_if_replaced()?;
+                continue;
+            };
+
+            if let Some(entry) = entry {
+                processor
+                    .process_entry(&entry.message, &entry.metadata)
+                    .unwrap_or_else(|err| {
+                        error!("Error processing line {}: {err}", entry.message);
+                    });
+            }
+            lines_processed += 1;
+
+            if lines_processed % 5000 == 0 {
+                report_input_progress(lines_processed, &input, start_time);
+            }
+        }
+        report_input_progress(lines_processed, &input, start_time);
+        save_state(state_files.as_ref(), &input, processor)?;
+
+        Ok(reader.ansi_cleaned_lines())
+    }
+
     pub fn stdout_writer(&self) -> &W {
         &self.stdout_writer
     }
 }
 
+/// Saves the state of the processor to the state file, if one is used, along
+/// with how much of the log file was parsed.
+fn save_state(
+    state_files: Option<&(PathBuf, PathBuf)>,
+    input: &Input,
+    processor: &LogProcessor,
+) -> Result<()> {
+    let Some((state_file, log_file)) = state_files else {
+        return Ok(());
+    };
+    let Some(offset) = input.resume_offset() else {
+        info!("Not saving state, {} can't be resumed", log_file.display());
+        return Ok(());
+    };
+    SavedState::save(state_file, log_file, offset, processor.state())
+}
+
+fn report_input_progress(lines_processed: usize, input: &Input, start_time: Instant) {
+    report_progress(
+        lines_processed,
+        input.bytes_read(),
+        input.total_bytes(),
+        start_time,
+    );
+}
+
+fn report_progress(
+    lines_processed: usize,
+    bytes_read: u64,
+    total_bytes: Option<u64>,
+    start_time: Instant,
+) {
+    let elapsed_time = start_time.elapsed().as_secs();
+    match total_bytes {
+        Some(total_bytes) if total_bytes > 0 => {
+            let percentage = (bytes_read as f64 / total_bytes as f64) * 100.0;
+            info!(
+                "Processed {} lines, {} out of {} bytes ({:.2}%) in {} seconds",
+                lines_processed, bytes_read, total_bytes, percentage, elapsed_time
+            );
+        }
+        _ => info!(
+            "Processed {} lines ({} bytes) in {} seconds",
+            lines_processed, bytes_read, elapsed_time
+        ),
+    }
+}
+
 pub struct RunnerBuilder<W: Write> {
-    log_file: Option<String>,
+    source: Option<LogSource>,
     stdout_writer: Option<W>,
+    format: InputFormat,
+    timestamp_priority: Option<Vec<TimestampSource>>,
+    follow: Option<Duration>,
+    state_file: Option<PathBuf>,
+    export_file: Option<PathBuf>,
+    parallel: bool,
 }
 
 impl<W: Write> Default for RunnerBuilder<W> {
     fn default() -> Self {
         RunnerBuilder {
-            log_file: None,
+            source: None,
             stdout_writer: None,
+            format: InputFormat::default(),
+            timestamp_priority: None,
+            follow: None,
+            state_file: None,
+            export_file: None,
+            parallel: false,
         }
     }
 }
 
 impl<W: Write> RunnerBuilder<W> {
+    /// Reads logs from the given path, `-` reads them from stdin.
     pub fn with_log_file(mut self, log_file: &str) -> Self {
-        self.log_file = Some(log_file.to_string());
+        self.source = Some(LogSource::from_arg(log_file));
+        self
+    }
+
+    /// Reads logs from an arbitrary, possibly non-seekable, reader.
+    pub fn with_reader<R: Read + 'static>(mut self, reader: R) -> Self {
+        self.source = Some(LogSource::Reader(Box::new(reader)));
+        self
+    }
+
+    pub fn with_source(mut self, source: LogSource) -> Self {
+        self.source = Some(source);
         self
     }
 
@@ -92,14 +264,70 @@ impl<W: Write> RunnerBuilder<W> {
         self
     }
 
+    /// Sets the format of the input, by default it is detected from its first
+    /// line.
+    pub fn with_format(mut self, format: InputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets which timestamp sources win when a line has several, by default
+    /// [`TimestampSource::DEFAULT_PRIORITY`].
+    pub fn with_timestamp_priority(mut self, priority: Vec<TimestampSource>) -> Self {
+        self.timestamp_priority = Some(priority);
+        self
+    }
+
+    /// Keeps reading lines appended to the log file once its end is reached,
+    /// like `tail -F`, printing the summary so far every `refresh_interval`.
+    pub fn with_follow(mut self, refresh_interval: Duration) -> Self {
+        self.follow = Some(refresh_interval);
+        self
+    }
+
+    /// Saves the parser state to `state_file` after the run and resumes from
+    /// it on the next one, so that only lines appended to the log file since
+    /// are parsed. The log is parsed from the start when it was rotated or
+    /// rewritten in between.
+    pub fn with_state_file(mut self, state_file: impl AsRef<Path>) -> Self {
+        self.state_file = Some(state_file.as_ref().to_path_buf());
+        self
+    }
+
+    /// Writes the stage durations and the node health reported by `Status`
+    /// lines of every pipeline and session to `export_file`, as JSON.
+    pub fn with_export_file(mut self, export_file: impl AsRef<Path>) -> Self {
+        self.export_file = Some(export_file.as_ref().to_path_buf());
+        self
+    }
+
+    /// Parses the log file by memory mapping it and splitting it in chunks
+    /// parsed on all CPUs, with the same results as the sequential parser.
+    /// Logs that can't be split, like compressed files or formats whose
+    /// entries span several lines, are parsed sequentially.
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
     pub fn build(self) -> Result<Runner<W>> {
+        if self.parallel && (self.follow.is_some() || self.state_file.is_some()) {
+            eyre::bail!("parallel parsing can't be combined with follow mode or a state file");
+        }
+
         Ok(Runner {
-            log_file: self
-                .log_file
-                .ok_or_else(|| eyre::eyre!("log_file is required"))?,
+            source: Some(self.source.ok_or_else(|| {
+                eyre::eyre!("a log source is required (file, directory, glob, stdin or reader)")
+            })?),
             stdout_writer: self
                 .stdout_writer
                 .ok_or_else(|| eyre::eyre!("stdout_writer is required"))?,
+            format: self.format,
+            timestamp_priority: self.timestamp_priority,
+            follow: self.follow,
+            state_file: self.state_file,
+            export_file: self.export_file,
+            parallel: self.parallel,
         })
     }
 }
@@ -121,7 +349,9 @@ mod tests {
             .build()
             .unwrap();
 
-        assert_eq!(runner.log_file, log_file.path().to_str().unwrap());
+        assert!(
+            matches!(runner.source, Some(LogSource::File(ref path)) if path == log_file.path())
+        );
     }
 
     #[test]
@@ -147,6 +377,158 @@ mod tests {
         assert!(output_str.contains("Total Pipeline Duration"));
     }
 
+    #[test]
+    fn test_runner_run_from_reader() {
+        let log = "2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=20037711 target=None
+2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=20038569 target=None stage_progress=100.00%
+";
+        let stdout_writer: Cursor<Vec<u8>> = Cursor::new(Vec::new());
+
+        let mut runner = Runner::builder()
+            .with_reader(Cursor::new(log.as_bytes().to_vec()))
+            .with_stdout_writer(stdout_writer)
+            .build()
+            .unwrap();
+
+        runner.run().unwrap();
+
+        let output_str = String::from_utf8(runner.stdout_writer.into_inner()).unwrap();
+        assert!(output_str.contains("Stage 001 - Headers: 1m 0s"));
+    }
+
+    #[test]
+    fn test_runner_run_rotated_set() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("reth.log.1"), "2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=20037711 target=None\n").unwrap();
+        std::fs::write(dir.path().join("reth.log"), "2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=20038569 target=None stage_progress=100.00%\n").unwrap();
+        let stdout_writer: Cursor<Vec<u8>> = Cursor::new(Vec::new());
+
+        let mut runner = Runner::builder()
+            .with_log_file(dir.path().to_str().unwrap())
+            .with_stdout_writer(stdout_writer)
+            .build()
+            .unwrap();
+
+        runner.run().unwrap();
+
+        let output_str = String::from_utf8(runner.stdout_writer.into_inner()).unwrap();
+        assert!(output_str.contains("Stage 001 - Headers: 1m 0s"));
+    }
+
+    #[test]
+    fn test_runner_run_timestamp_priority() {
+        let log = "Jun 07 09:05:20 reth-5 reth-mainnet[4009735]: 2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=20037711 target=None
+Jun 07 09:07:20 reth-5 reth-mainnet[4009735]: 2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=20038569 target=None stage_progress=100.00%
+";
+        let stdout_writer: Cursor<Vec<u8>> = Cursor::new(Vec::new());
+
+        let mut runner = Runner::builder()
+            .with_reader(Cursor::new(log.as_bytes().to_vec()))
+            .with_stdout_writer(stdout_writer)
+            .with_timestamp_priority(vec![TimestampSource::Syslog])
+            .build()
+            .unwrap();
+
+        runner.run().unwrap();
+
+        let output_str = String::from_utf8(runner.stdout_writer.into_inner()).unwrap();
+        assert!(output_str.contains("Stage 001 - Headers: 2m 0s"));
+    }
+
+    #[test]
+    fn test_runner_run_export() {
+        let log = "2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=20037711 target=None
+2024-06-07T09:05:45.873354Z  INFO Status connected_peers=10 freelist=4 stage=Headers checkpoint=20037711 target=None stage_progress=50.00%
+2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=20038569 target=None stage_progress=100.00%
+thread 'main' panicked at bin/reth/src/main.rs:35:9:
+database is locked
+";
+        let dir = tempfile::tempdir().unwrap();
+        let export_file = dir.path().join("export.json");
+
+        let mut runner = Runner::builder()
+            .with_reader(Cursor::new(log.as_bytes().to_vec()))
+            .with_stdout_writer(Cursor::new(Vec::new()))
+            .with_export_file(&export_file)
+            .build()
+            .unwrap();
+        runner.run().unwrap();
+
+        let output_str = String::from_utf8(runner.stdout_writer.into_inner()).unwrap();
+        assert!(output_str.contains("Node Health: 1 status report, peers 10-10"));
+
+        let export: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&export_file).unwrap()).unwrap();
+        let pipeline = &export["pipelines"][0];
+        assert_eq!(pipeline["stages"]["001 - Headers"], 60.0);
+        assert_eq!(pipeline["health"]["max_peers"], 10);
+        assert_eq!(
+            pipeline["status"][0]["timestamp"],
+            "2024-06-07T09:05:45.873354Z"
+        );
+        assert_eq!(export["sessions"][0]["status"][0]["freelist"], 4);
+        assert_eq!(pipeline["abort"]["reason"], "Panic");
+        assert_eq!(pipeline["abort"]["message"][0], "database is locked");
+    }
+
+    #[test]
+    fn test_runner_run_issues() {
+        let log = "2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=20037711 target=None
+2024-06-07T09:05:45.873354Z  WARN net::session: Session error peer_id=0x01 err=timeout
+2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=20038569 target=None stage_progress=100.00%
+";
+        let mut runner = Runner::builder()
+            .with_reader(Cursor::new(log.as_bytes().to_vec()))
+            .with_stdout_writer(Cursor::new(Vec::new()))
+            .build()
+            .unwrap();
+        runner.run().unwrap();
+
+        let output_str = String::from_utf8(runner.stdout_writer.into_inner()).unwrap();
+        let (summary, issues) = output_str.split_once("Warnings and Errors:").unwrap();
+        assert!(summary.contains("Total Aggregate Duration: 1m 0s"));
+        assert!(issues.contains("1x WARN net::session: Session error peer_id=<peer> err=timeout"));
+        assert!(issues.contains("during Headers"));
+    }
+
+    fn run_with_state(log_file: &Path, state_file: &Path) -> String {
+        let mut runner = Runner::builder()
+            .with_log_file(log_file.to_str().unwrap())
+            .with_stdout_writer(Cursor::new(Vec::new()))
+            .with_state_file(state_file)
+            .build()
+            .unwrap();
+        runner.run().unwrap();
+        String::from_utf8(runner.stdout_writer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_runner_run_incremental() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("state.json");
+        let log_file = dir.path().join("reth.log");
+        let start = "2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=20037711 target=None\n";
+        let end = "2024-06-07T09:06:20.873354Z  INFO Finished stage pipeline_stages=1/12 stage=Headers checkpoint=20038569 target=None stage_progress=100.00%\n";
+
+        std::fs::write(&log_file, start).unwrap();
+        let output = run_with_state(&log_file, &state_file);
+        assert!(output.contains("Stage 001 - Headers: 0s (in progress at the end of the log)"));
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&log_file)
+            .unwrap();
+        file.write_all(end.as_bytes()).unwrap();
+        let output = run_with_state(&log_file, &state_file);
+        assert!(output.contains("Stage 001 - Headers: 1m 0s"));
+        assert_eq!(output.matches("Pipeline 1").count(), 1);
+
+        // A rewritten log doesn't match the saved state and is parsed again.
+        std::fs::write(&log_file, end).unwrap();
+        let output = run_with_state(&log_file, &state_file);
+        assert!(!output.contains("Stage 001 - Headers"));
+    }
+
     #[test]
     fn test_runner_builder_missing_log_file() {
         let stdout_writer: Cursor<Vec<u8>> = Cursor::new(Vec::new());
@@ -0,0 +1,138 @@
+use crate::{
+    health::{HealthSummary, StatusSample},
+    time::{format_duration, format_time},
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// One lifetime of the reth process, from a startup banner or the first line
+/// written by a new PID, to the last line it wrote.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// `None` when the log doesn't tell which process wrote it.
+    pub pid: Option<u32>,
+    /// Timestamp of the first event of the session.
+    pub start: Option<SystemTime>,
+    /// Timestamp of the last event of the session.
+    pub end: Option<SystemTime>,
+    /// Health of the node reported by `Status` lines during the session.
+    pub status: Vec<StatusSample>,
+}
+
+impl Session {
+    pub fn new(pid: Option<u32>) -> Self {
+        Session {
+            pid,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a line written by `pid` belongs to another process.
+    pub(crate) fn is_other_process(&self, pid: Option<u32>) -> bool {
+        matches!((self.pid, pid), (Some(current), Some(pid)) if current != pid)
+    }
+
+    /// Extends the session to an event which happened at `timestamp`.
+    pub(crate) fn record(&mut self, pid: Option<u32>, timestamp: Option<SystemTime>) {
+        self.pid = self.pid.or(pid);
+        if let Some(timestamp) = timestamp {
+            self.start.get_or_insert(timestamp);
+            self.end = Some(timestamp);
+        }
+    }
+
+    /// Time reth was down between the end of `previous` and the start of this
+    /// session, `None` when unknown or if the sessions overlap.
+    pub fn downtime_after(&self, previous: &Session) -> Option<Duration> {
+        self.start?.duration_since(previous.end?).ok()
+    }
+
+    /// Prints the sessions with the pipelines that ran in each of them, given
+    /// the session of every pipeline, and the downtime between them.
+    pub(crate) fn print_summary<W: std::io::Write>(
+        sessions: &[Session],
+        pipeline_sessions: &[usize],
+        writer: &mut W,
+    ) {
+        writeln!(writer, "Sessions:").unwrap();
+        let mut total_downtime = Duration::ZERO;
+        for (index, session) in sessions.iter().enumerate() {
+            let mut description = format!("  Session {}", index + 1);
+            if let Some(pid) = session.pid {
+                description.push_str(&format!(" (PID {pid})"));
+            }
+            description.push_str(&format!(
+                ": {} - {}, {}",
+                format_time(session.start),
+                format_time(session.end),
+                describe_pipelines(index, pipeline_sessions)
+            ));
+            if let Some(previous) = index.checked_sub(1).map(|previous| &sessions[previous]) {
+                match session.downtime_after(previous) {
+                    Some(downtime) => {
+                        total_downtime += downtime;
+                        description
+                            .push_str(&format!(", after {} down", format_duration(&downtime)));
+                    }
+                    None => description.push_str(", overlapping the previous session"),
+                }
+            }
+            writeln!(writer, "{description}").unwrap();
+            if let Some(health) = HealthSummary::new(&session.status) {
+                writeln!(writer, "    Node Health: {}", health.describe()).unwrap();
+            }
+        }
+        writeln!(
+            writer,
+            "  Total Downtime: {}",
+            format_duration(&total_downtime)
+        )
+        .unwrap();
+    }
+}
+
+/// Lists the pipelines of a session, e.g. `pipelines 2-4`.
+fn describe_pipelines(session: usize, pipeline_sessions: &[usize]) -> String {
+    let pipelines: Vec<usize> = pipeline_sessions
+        .iter()
+        .enumerate()
+        .filter(|(_, pipeline_session)| **pipeline_session == session)
+        .map(|(index, _)| index + 1)
+        .collect();
+    match pipelines.as_slice() {
+        [] => "no pipeline".to_string(),
+        [pipeline] => format!("pipeline {pipeline}"),
+        [first, .., last] => format!("pipelines {first}-{last}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_downtime() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_718_000_000);
+        let mut first = Session::new(Some(1));
+        first.record(None, Some(start));
+        first.record(None, Some(start + Duration::from_secs(60)));
+        let mut second = Session::new(None);
+        second.record(Some(2), Some(start + Duration::from_secs(120)));
+
+        assert!(first.is_other_process(Some(2)));
+        assert!(!first.is_other_process(None));
+        assert_eq!(second.pid, Some(2));
+        assert_eq!(second.downtime_after(&first), Some(Duration::from_secs(60)));
+        assert_eq!(first.downtime_after(&second), None);
+
+        let mut output = Vec::new();
+        Session::print_summary(&[first, second], &[0, 1, 1], &mut output);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Sessions:\n  \
+             Session 1 (PID 1): 2024-06-10 06:13:20 - 2024-06-10 06:14:20, pipeline 1\n  \
+             Session 2 (PID 2): 2024-06-10 06:15:20 - 2024-06-10 06:15:20, pipelines 2-3, after 1m 0s down\n  \
+             Total Downtime: 1m 0s\n"
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use crate::log_processor::ProcessorState;
+use eyre::Result;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// Bumped whenever the saved state changes in a way older versions can't read.
+const STATE_VERSION: u32 = 1;
+
+/// How many bytes at the start of the log and before the saved offset are
+/// hashed to recognise the log the state was saved for.
+const FINGERPRINT_LEN: u64 = 4096;
+
+/// Parser state saved after a run, so that the next one only parses the
+/// lines appended to the log since.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SavedState {
+    version: u32,
+    /// Offset of the log up to which lines were parsed.
+    pub(crate) offset: u64,
+    fingerprint: Fingerprint,
+    pub(crate) processor: ProcessorState,
+}
+
+impl SavedState {
+    /// Loads the state saved for `log_file`. Returns `None` when there is no
+    /// state yet or it can't be used, because it was saved by another version
+    /// or the log was rotated or rewritten since, in which case the log has to
+    /// be parsed from the start.
+    pub(crate) fn load(state_file: &Path, log_file: &Path) -> Result<Option<Self>> {
+        let contents = match fs::read(state_file) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let state: SavedState = match serde_json::from_slice(&contents) {
+            Ok(state) => state,
+            Err(err) => {
+                info!(
+                    "Ignoring unreadable state file {}: {err}",
+                    state_file.display()
+                );
+                return Ok(None);
+            }
+        };
+        if state.version != STATE_VERSION {
+            info!(
+                "Ignoring state file {} saved by another version",
+                state_file.display()
+            );
+            return Ok(None);
+        }
+
+        let mut file = File::open(log_file)?;
+        if file.metadata()?.len() < state.offset
+            || Fingerprint::new(&mut file, state.offset)? != state.fingerprint
+        {
+            info!(
+                "{} changed since the state was saved, parsing it from the start",
+                log_file.display()
+            );
+            return Ok(None);
+        }
+
+        Ok(Some(state))
+    }
+
+    /// Saves the state of the processor after parsing `log_file` up to
+    /// `offset`, replacing the state file atomically.
+    pub(crate) fn save(
+        state_file: &Path,
+        log_file: &Path,
+        offset: u64,
+        processor: ProcessorState,
+    ) -> Result<()> {
+        let state = SavedState {
+            version: STATE_VERSION,
+            offset,
+            fingerprint: Fingerprint::new(&mut File::open(log_file)?, offset)?,
+            processor,
+        };
+
+        let mut temp_file = state_file.as_os_str().to_owned();
+        temp_file.push(".tmp");
+        fs::write(&temp_file, serde_json::to_vec(&state)?)?;
+        fs::rename(&temp_file, state_file)?;
+        Ok(())
+    }
+}
+
+/// Hashes of the start of the log and of the bytes before the saved offset.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Fingerprint {
+    head: u64,
+    tail: u64,
+}
+
+impl Fingerprint {
+    fn new(file: &mut File, offset: u64) -> io::Result<Self> {
+        let len = FINGERPRINT_LEN.min(offset);
+        Ok(Fingerprint {
+            head: hash_range(file, 0, len)?,
+            tail: hash_range(file, offset - len, len)?,
+        })
+    }
+}
+
+/// FNV-1a hash of `len` bytes of the file starting at `start`.
+fn hash_range(file: &mut File, start: u64, len: u64) -> io::Result<u64> {
+    let mut buf = Vec::with_capacity(len as usize);
+    file.seek(SeekFrom::Start(start))?;
+    file.take(len).read_to_end(&mut buf)?;
+    Ok(buf.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_processor::LogProcessor;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn save(state_file: &Path, log_file: &Path, offset: u64) {
+        let processor = LogProcessor::new().unwrap();
+        SavedState::save(state_file, log_file, offset, processor.state()).unwrap();
+    }
+
+    #[test]
+    fn test_saved_state_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("state.json");
+        let mut log_file = NamedTempFile::new().unwrap();
+        write!(log_file, "first\nsecond\n").unwrap();
+
+        assert!(SavedState::load(&state_file, log_file.path())
+            .unwrap()
+            .is_none());
+
+        save(&state_file, log_file.path(), 6);
+        writeln!(log_file, "third").unwrap();
+        let state = SavedState::load(&state_file, log_file.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.offset, 6);
+    }
+
+    #[test]
+    fn test_saved_state_rewritten_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("state.json");
+        let log_file = dir.path().join("reth.log");
+
+        fs::write(&log_file, "first\nsecond\n").unwrap();
+        save(&state_file, &log_file, 13);
+
+        fs::write(&log_file, "other\nsecond\n").unwrap();
+        assert!(SavedState::load(&state_file, &log_file).unwrap().is_none());
+
+        fs::write(&log_file, "first\n").unwrap();
+        assert!(SavedState::load(&state_file, &log_file).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_saved_state_other_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("state.json");
+        let log_file = dir.path().join("reth.log");
+        fs::write(&log_file, "first\n").unwrap();
+        save(&state_file, &log_file, 6);
+
+        let mut state: serde_json::Value =
+            serde_json::from_slice(&fs::read(&state_file).unwrap()).unwrap();
+        state["version"] = (STATE_VERSION + 1).into();
+        fs::write(&state_file, state.to_string()).unwrap();
+        assert!(SavedState::load(&state_file, &log_file).unwrap().is_none());
+
+        fs::write(&state_file, "not json").unwrap();
+        assert!(SavedState::load(&state_file, &log_file).unwrap().is_none());
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{
+    stats::{percentile, Stats},
+    time::format_elapsed,
+};
+use serde::{Deserialize, Serialize};
+
+/// Validations taking longer than this many seconds are flagged as slow.
+const SLOW_THRESHOLD: f64 = 1.0;
+
+/// How many of the slowest validations are listed.
+const SLOWEST_LISTED: usize = 5;
+
+/// A state root validation, as logged by `Validated state root` lines.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StateRootValidation {
+    /// Block the state root was validated for, when the line tells.
+    pub block: Option<u64>,
+    /// Time the validation took, in seconds.
+    pub elapsed: f64,
+}
+
+/// Distribution of the time state root validations took.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateRootSummary {
+    pub stats: Stats,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    /// Validations slower than [`SLOW_THRESHOLD`], slowest first.
+    pub slow: Vec<StateRootValidation>,
+}
+
+impl StateRootSummary {
+    /// Summarises the validations, `None` if there are none.
+    pub fn new<'v>(validations: impl IntoIterator<Item = &'v StateRootValidation>) -> Option<Self> {
+        let mut validations: Vec<StateRootValidation> = validations.into_iter().copied().collect();
+        if validations.is_empty() {
+            return None;
+        }
+        validations.sort_by(|a, b| b.elapsed.total_cmp(&a.elapsed));
+
+        let mut stats = Stats::default();
+        for validation in &validations {
+            stats.update(validation.elapsed);
+        }
+        let elapsed: Vec<f64> = validations
+            .iter()
+            .rev()
+            .map(|validation| validation.elapsed)
+            .collect();
+
+        Some(StateRootSummary {
+            p50: percentile(&elapsed, 50.0),
+            p90: percentile(&elapsed, 90.0),
+            p99: percentile(&elapsed, 99.0),
+            slow: validations
+                .iter()
+                .take_while(|validation| validation.elapsed > SLOW_THRESHOLD)
+                .copied()
+                .collect(),
+            stats,
+        })
+    }
+
+    /// Prints the summary under `title`, with the slowest validations on the
+    /// following line.
+    pub(crate) fn print<W: std::io::Write>(&self, title: &str, indent: &str, writer: &mut W) {
+        writeln!(
+            writer,
+            "{indent}{title}: {} validation{}, mean {}, std {}, min {}, max {}, p50 {}, p90 {}, p99 {}",
+            self.stats.count,
+            if self.stats.count == 1 { "" } else { "s" },
+            format_elapsed(self.stats.mean),
+            format_elapsed(self.stats.std_dev()),
+            format_elapsed(self.stats.min),
+            format_elapsed(self.stats.max),
+            format_elapsed(self.p50),
+            format_elapsed(self.p90),
+            format_elapsed(self.p99)
+        )
+        .unwrap();
+
+        if self.slow.is_empty() {
+            return;
+        }
+        let slowest: Vec<String> = self
+            .slow
+            .iter()
+            .take(SLOWEST_LISTED)
+            .map(|validation| match validation.block {
+                Some(block) => format!("block {block} ({})", format_elapsed(validation.elapsed)),
+                None => format_elapsed(validation.elapsed),
+            })
+            .collect();
+        writeln!(
+            writer,
+            "{indent}  {} slower than {}: {}",
+            self.slow.len(),
+            format_elapsed(SLOW_THRESHOLD),
+            slowest.join(", ")
+        )
+        .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_root_summary() {
+        let validations: Vec<StateRootValidation> = (1..=100)
+            .map(|block| StateRootValidation {
+                block: Some(block),
+                elapsed: block as f64 / 64.0,
+            })
+            .collect();
+
+        let summary = StateRootSummary::new(&validations).unwrap();
+        assert_eq!(summary.stats.count, 100);
+        assert_eq!(summary.p50, 50.0 / 64.0);
+        assert_eq!(summary.p90, 90.0 / 64.0);
+        assert_eq!(summary.p99, 99.0 / 64.0);
+        assert_eq!(summary.slow.len(), 36);
+
+        let mut output = Vec::new();
+        summary.print("State Root", "  ", &mut output);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "  State Root: 100 validations, mean 789.1ms, std 451.0ms, min 15.6ms, max 1.56s, \
+             p50 781.2ms, p90 1.41s, p99 1.55s\n    \
+             36 slower than 1.00s: block 100 (1.56s), block 99 (1.55s), block 98 (1.53s), \
+             block 97 (1.52s), block 96 (1.50s)\n"
+        );
+        assert!(StateRootSummary::new(&[]).is_none());
+    }
+}
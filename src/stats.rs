@@ -1,16 +1,42 @@
-#[derive(Default, Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Stats {
     pub count: usize,
     pub mean: f64,
     pub m2: f64,
+    pub min: f64,
+    pub max: f64,
 }
 
 impl Stats {
     pub fn update(&mut self, value: f64) {
         self.count += 1;
+        if self.count == 1 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
         let delta = value - self.mean;
         self.mean += delta / self.count as f64;
         let delta2 = value - self.mean;
         self.m2 += delta * delta2;
     }
+
+    /// Population standard deviation of the values.
+    pub fn std_dev(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        (self.m2 / self.count as f64).sqrt()
+    }
+}
+
+/// Nearest-rank percentile of values sorted in ascending order, which must not
+/// be empty.
+pub(crate) fn percentile(sorted_values: &[f64], percent: f64) -> f64 {
+    let rank = (percent / 100.0 * sorted_values.len() as f64).ceil() as usize;
+    sorted_values[rank.clamp(1, sorted_values.len()) - 1]
 }
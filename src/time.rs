@@ -1,3 +1,5 @@
+use crate::format::EntryMetadata;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Utc};
 use eyre::Result;
 use regex::Regex;
 use std::time::{Duration, SystemTime};
@@ -17,15 +19,229 @@ pub(crate) fn format_duration(duration: &Duration) -> String {
     }
 }
 
-pub(crate) fn extract_timestamp(line: &str) -> Result<SystemTime> {
-    let timestamp_str = Regex::new(r"(\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d{6}Z)")?
-        .captures(line)
-        .and_then(|caps| caps.get(1))
-        .map(|m| m.as_str())
-        .ok_or_else(|| eyre::eyre!("Failed to extract timestamp"))?;
+/// Formats a number of seconds with the precision of per-block timings, e.g.
+/// `152.3ms` or `2.50s`.
+pub(crate) fn format_elapsed(seconds: f64) -> String {
+    if seconds < 1.0 {
+        format!("{:.1}ms", seconds * 1000.0)
+    } else {
+        format!("{seconds:.2}s")
+    }
+}
+
+/// Formats a timestamp to the second in UTC, `?` when unknown.
+pub(crate) fn format_time(timestamp: Option<SystemTime>) -> String {
+    match timestamp {
+        Some(timestamp) => DateTime::<Utc>::from(timestamp)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+        None => "?".to_string(),
+    }
+}
+
+/// Where the timestamp of a log line can be taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampSource {
+    /// The RFC 3339 timestamp written by reth, either in text or JSON lines.
+    Reth,
+    /// The timestamp recorded by the log collector: journald, Docker or CRI.
+    Collector,
+    /// A syslog style prefix such as `Jun 04 20:44:41`, read as UTC.
+    Syslog,
+}
+
+impl TimestampSource {
+    /// Sources in the order they are tried by default, the most precise first.
+    pub const DEFAULT_PRIORITY: [TimestampSource; 3] = [
+        TimestampSource::Reth,
+        TimestampSource::Collector,
+        TimestampSource::Syslog,
+    ];
+
+    /// Parses the name used on the command line.
+    pub fn from_name(name: &str) -> Result<Self> {
+        Ok(match name {
+            "reth" => TimestampSource::Reth,
+            "collector" => TimestampSource::Collector,
+            "syslog" => TimestampSource::Syslog,
+            _ => eyre::bail!("unknown timestamp source {name}"),
+        })
+    }
+}
+
+/// Extracts the timestamp of log lines, which may carry several of them, from
+/// the first [`TimestampSource`] of a priority list that provides one.
+#[derive(Clone)]
+pub(crate) struct TimestampExtractor {
+    rfc3339: Regex,
+    syslog: Regex,
+    priority: Vec<TimestampSource>,
+    /// Last timestamp extracted, used to infer the year of syslog prefixes.
+    reference: Option<DateTime<Utc>>,
+}
+
+impl TimestampExtractor {
+    pub(crate) fn new() -> Result<Self> {
+        Ok(TimestampExtractor {
+            rfc3339: Regex::new(
+                r"\d{4}-\d{2}-\d{2}[Tt ]\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:[Zz]|[+-]\d{2}:?\d{2})",
+            )?,
+            syslog: Regex::new(r"^([A-Z][a-z]{2}) +(\d{1,2}) (\d{2}):(\d{2}):(\d{2})\b")?,
+            priority: TimestampSource::DEFAULT_PRIORITY.to_vec(),
+            reference: None,
+        })
+    }
+
+    pub(crate) fn set_priority(&mut self, priority: Vec<TimestampSource>) {
+        self.priority = priority;
+    }
+
+    /// Last timestamp extracted, saved with the parser state so that a
+    /// resumed run infers the same syslog years.
+    pub(crate) fn reference(&self) -> Option<SystemTime> {
+        self.reference.map(SystemTime::from)
+    }
+
+    pub(crate) fn set_reference(&mut self, reference: Option<SystemTime>) {
+        self.reference = reference.map(DateTime::<Utc>::from);
+    }
+
+    /// Whether a text line carries a timestamp written by reth, as its log
+    /// lines do, unlike what it writes to stderr.
+    pub(crate) fn is_timestamped(&self, line: &str) -> bool {
+        self.rfc3339.is_match(line)
+    }
+
+    /// Timestamp written by reth in a text line, which has a full date.
+    pub(crate) fn reth_timestamp(&self, line: &str) -> Option<SystemTime> {
+        self.rfc3339
+            .find(line)
+            .and_then(|timestamp| parse_rfc3339(timestamp.as_str()))
+    }
+
+    /// Extracts the timestamp of a text line.
+    pub(crate) fn extract(&mut self, line: &str, metadata: &EntryMetadata) -> Result<SystemTime> {
+        let reth = self.reth_timestamp(line);
+        self.pick(reth, line, metadata)
+    }
+
+    /// Chooses between the timestamp written by reth, already parsed from
+    /// the line by the caller, and the other sources.
+    pub(crate) fn pick(
+        &mut self,
+        reth: Option<SystemTime>,
+        line: &str,
+        metadata: &EntryMetadata,
+    ) -> Result<SystemTime> {
+        let mut syslog = None;
+        for source in &self.priority {
+            let timestamp = match source {
+                TimestampSource::Reth => reth,
+                TimestampSource::Collector => metadata.timestamp,
+                TimestampSource::Syslog => {
+                    let reference = reth.or(metadata.timestamp).map(DateTime::<Utc>::from);
+                    *syslog.get_or_insert_with(|| self.syslog_timestamp(line, reference))
+                }
+            };
+
+            if let Some(timestamp) = timestamp {
+                self.reference = Some(timestamp.into());
+                return Ok(timestamp);
+            }
+        }
+
+        Err(eyre::eyre!("Failed to extract timestamp"))
+    }
+
+    /// Parses a syslog prefix, picking the year that puts it closest to
+    /// `reference` or to the last timestamp seen.
+    fn syslog_timestamp(&self, line: &str, reference: Option<DateTime<Utc>>) -> Option<SystemTime> {
+        let caps = self.syslog.captures(line)?;
+        let month = MONTHS.iter().position(|month| *month == &caps[1])? as u32 + 1;
+        let day = caps[2].parse().ok()?;
+        let time = NaiveTime::from_hms_opt(
+            caps[3].parse().ok()?,
+            caps[4].parse().ok()?,
+            caps[5].parse().ok()?,
+        )?;
+
+        let (reference, latest) = match reference.or(self.reference) {
+            Some(reference) => (reference, None),
+            None => {
+                let now = Utc::now();
+                (now, Some(now + chrono::Duration::days(1)))
+            }
+        };
+
+        [reference.year() - 1, reference.year(), reference.year() + 1]
+            .into_iter()
+            .filter_map(|year| NaiveDate::from_ymd_opt(year, month, day))
+            .map(|date| date.and_time(time).and_utc())
+            .filter(|candidate| latest.is_none_or(|latest| *candidate <= latest))
+            .min_by_key(|candidate| (*candidate - reference).abs())
+            .map(SystemTime::from)
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Parses an RFC 3339 timestamp with any fractional precision and offset,
+/// also accepting offsets without a colon, e.g. `+0000`.
+pub(crate) fn parse_rfc3339(timestamp: &str) -> Option<SystemTime> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .or_else(|_| DateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%.f%z"))
+        .ok()
+        .map(SystemTime::from)
+}
 
-    let dt = timestamp_str.parse::<chrono::DateTime<chrono::Utc>>()?;
-    Ok(SystemTime::from(dt))
+/// Parses a duration as printed by `Debug` for `std::time::Duration`, e.g.
+/// `1.5s` or `12.3ms`, into seconds.
+pub(crate) fn parse_elapsed(elapsed: &str) -> Option<f64> {
+    let split = elapsed.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+    let (value, unit) = elapsed.split_at(split);
+    let value: f64 = value.parse().ok()?;
+    let scale = match unit {
+        "s" => 1.0,
+        "ms" => 1e-3,
+        "µs" | "us" => 1e-6,
+        "ns" => 1e-9,
+        _ => return None,
+    };
+    Some(value * scale)
+}
+
+/// Parses the `stage_eta` reth logs, formatted like `1h 2m 3s` or
+/// `2days 3h 10m`, up to the first word which isn't part of it.
+pub(crate) fn parse_eta(eta: &str) -> Option<Duration> {
+    let mut seconds = 0.0;
+    let mut parsed = false;
+    for part in eta.split_whitespace() {
+        let Some(split) = part
+            .find(|c: char| !c.is_ascii_digit())
+            .filter(|split| *split > 0)
+        else {
+            break;
+        };
+        let (value, unit) = part.split_at(split);
+        let scale = match unit {
+            "years" | "year" | "y" => 31_557_600.0,
+            "months" | "month" | "M" => 2_630_016.0,
+            "weeks" | "week" | "w" => 604_800.0,
+            "days" | "day" | "d" => 86_400.0,
+            "h" => 3_600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 1e-3,
+            "us" | "µs" => 1e-6,
+            "ns" => 1e-9,
+            _ => break,
+        };
+        seconds += value.parse::<f64>().ok()? * scale;
+        parsed = true;
+    }
+    parsed.then(|| Duration::from_secs_f64(seconds))
 }
 
 #[cfg(test)]
@@ -33,6 +249,17 @@ mod tests {
     use super::*;
     use chrono::{TimeZone, Timelike};
 
+    #[test]
+    fn test_parse_eta() {
+        assert_eq!(parse_eta("1h 2m 3s"), Some(Duration::from_secs(3723)));
+        assert_eq!(
+            parse_eta("2days 3h target=1"),
+            Some(Duration::from_secs(2 * 86_400 + 3 * 3_600))
+        );
+        assert_eq!(parse_eta("unknown"), None);
+        assert_eq!(parse_eta(""), None);
+    }
+
     #[test]
     fn test_format_duration() {
         assert_eq!(format_duration(&Duration::new(59, 0)), "59s");
@@ -41,10 +268,28 @@ mod tests {
         assert_eq!(format_duration(&Duration::new(3661, 0)), "1h 1m");
     }
 
+    #[test]
+    fn test_parse_elapsed() {
+        assert_eq!(parse_elapsed("2.5s"), Some(2.5));
+        assert_eq!(parse_elapsed("12.5ms"), Some(0.0125));
+        assert_eq!(parse_elapsed("250µs"), Some(0.00025));
+        assert_eq!(parse_elapsed("1.5m"), None);
+        assert_eq!(parse_elapsed("fast"), None);
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, min: u32, sec: u32) -> SystemTime {
+        SystemTime::from(
+            chrono::Utc
+                .with_ymd_and_hms(year, month, day, hour, min, sec)
+                .unwrap(),
+        )
+    }
+
     #[test]
     fn test_extract_timestamp() {
+        let mut extractor = TimestampExtractor::new().unwrap();
         let line = "2024-06-07T09:05:20.873354Z  INFO Preparing stage pipeline_stages=1/12 stage=Headers checkpoint=20037711 target=None";
-        let timestamp = extract_timestamp(line).unwrap();
+        let timestamp = extractor.extract(line, &EntryMetadata::default()).unwrap();
         let expected_time = chrono::Utc
             .with_ymd_and_hms(2024, 6, 7, 9, 5, 20)
             .unwrap()
@@ -54,7 +299,98 @@ mod tests {
         assert_eq!(SystemTime::from(expected_time), timestamp);
 
         let line = "Invalid log line without a timestamp";
-        let result = extract_timestamp(line);
+        let result = extractor.extract(line, &EntryMetadata::default());
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_extract_timestamp_precision_and_offsets() {
+        let mut extractor = TimestampExtractor::new().unwrap();
+        let metadata = EntryMetadata::default();
+        let expected = utc(2024, 6, 7, 9, 5, 20);
+
+        for (line, nanos) in [
+            ("2024-06-07T09:05:20.873354123Z  INFO", 873_354_123),
+            ("2024-06-07T09:05:20.873Z  INFO", 873_000_000),
+            ("2024-06-07T09:05:20Z  INFO", 0),
+            ("2024-06-07T09:05:20.873354+00:00  INFO", 873_354_000),
+            ("2024-06-07T11:05:20.873354+0200  INFO", 873_354_000),
+            ("2024-06-07 09:05:20.873354Z  INFO", 873_354_000),
+        ] {
+            assert_eq!(
+                extractor.extract(line, &metadata).unwrap(),
+                expected + Duration::from_nanos(nanos),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_extract_syslog_timestamp() {
+        let mut extractor = TimestampExtractor::new().unwrap();
+        let metadata = EntryMetadata::default();
+
+        let line =
+            "Dec 31 23:59:59 reth-5 reth-mainnet[4009735]: 2023-12-31T23:59:59.000000Z  INFO";
+        assert_eq!(
+            extractor.extract(line, &metadata).unwrap(),
+            utc(2023, 12, 31, 23, 59, 59)
+        );
+
+        // The year is inferred from the previous line, across new year.
+        let line = "Jan  1 00:00:01 reth-5 reth-mainnet[4009735]: INFO";
+        assert_eq!(
+            extractor.extract(line, &metadata).unwrap(),
+            utc(2024, 1, 1, 0, 0, 1)
+        );
+        let line = "Jun 04 20:44:41 reth-5 reth-mainnet[4009735]: INFO";
+        assert_eq!(
+            extractor.extract(line, &metadata).unwrap(),
+            utc(2024, 6, 4, 20, 44, 41)
+        );
+    }
+
+    #[test]
+    fn test_extract_syslog_timestamp_without_reference() {
+        let mut extractor = TimestampExtractor::new().unwrap();
+        let line = "Jun 04 20:44:41 reth-5 reth-mainnet[4009735]: INFO";
+
+        let timestamp = extractor.extract(line, &EntryMetadata::default()).unwrap();
+        let timestamp = DateTime::<Utc>::from(timestamp);
+        assert!(timestamp <= Utc::now() + chrono::Duration::days(1));
+        assert!(timestamp > Utc::now() - chrono::Duration::days(366));
+        assert_eq!((timestamp.month(), timestamp.day()), (6, 4));
+    }
+
+    #[test]
+    fn test_extract_timestamp_priority() {
+        let mut extractor = TimestampExtractor::new().unwrap();
+        let line =
+            "Jun 04 20:44:42 reth-5 reth-mainnet[4009735]: 2024-06-04T20:44:41.293617Z  INFO";
+        let metadata = EntryMetadata {
+            timestamp: Some(utc(2024, 6, 4, 20, 44, 43)),
+            ..Default::default()
+        };
+
+        let reth = utc(2024, 6, 4, 20, 44, 41) + Duration::from_micros(293617);
+        assert_eq!(extractor.extract(line, &metadata).unwrap(), reth);
+
+        extractor.set_priority(vec![TimestampSource::Syslog, TimestampSource::Reth]);
+        assert_eq!(
+            extractor.extract(line, &metadata).unwrap(),
+            utc(2024, 6, 4, 20, 44, 42)
+        );
+
+        extractor.set_priority(vec![TimestampSource::Collector]);
+        assert_eq!(
+            extractor.extract(line, &metadata).unwrap(),
+            utc(2024, 6, 4, 20, 44, 43)
+        );
+        assert!(extractor
+            .extract(
+                "2024-06-04T20:44:41.293617Z  INFO",
+                &EntryMetadata::default()
+            )
+            .is_err());
+    }
 }
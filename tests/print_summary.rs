@@ -11,7 +11,35 @@ use std::{fs::File, io::Cursor, io::Read, path::Path};
     "/tests/data/input-print-summary-multiple-pipelines.txt",
     "/tests/data/output-print-summary-multiple-pipelines.txt"
 )]
-fn test_e2e_print_summary(#[case] input_file_path: &str, #[case] expected_output_path: &str) {
+#[case(
+    "/tests/data/input-print-summary-ansi.txt",
+    "/tests/data/output-print-summary-basic.txt"
+)]
+#[case(
+    "/tests/data/input-print-summary-reth-json.txt",
+    "/tests/data/output-print-summary-basic.txt"
+)]
+#[case(
+    "/tests/data/input-print-summary-docker.json",
+    "/tests/data/output-print-summary-basic.txt"
+)]
+#[case(
+    "/tests/data/input-print-summary-cri.txt",
+    "/tests/data/output-print-summary-basic.txt"
+)]
+#[case(
+    "/tests/data/input-print-summary-journald.json",
+    "/tests/data/output-print-summary-journald.txt"
+)]
+#[case(
+    "/tests/data/input-print-summary-journald.export",
+    "/tests/data/output-print-summary-journald.txt"
+)]
+fn test_e2e_print_summary(
+    #[case] input_file_path: &str,
+    #[case] expected_output_path: &str,
+    #[values(false, true)] parallel: bool,
+) {
     let log_file_path = format!("{}{}", env!("CARGO_MANIFEST_DIR"), input_file_path);
     let stdout_writer: Cursor<Vec<u8>> = Cursor::new(Vec::new());
     let log_file = Path::new(&log_file_path);
@@ -19,6 +47,7 @@ fn test_e2e_print_summary(#[case] input_file_path: &str, #[case] expected_output
     let mut runner = Runner::builder()
         .with_log_file(log_file.to_str().unwrap())
         .with_stdout_writer(stdout_writer)
+        .with_parallel(parallel)
         .build()
         .unwrap();
 